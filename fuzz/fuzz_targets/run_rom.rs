@@ -0,0 +1,30 @@
+// Executes arbitrary bytes as a program. Any panic is a bug; errors are fine
+// Run with: cargo +nightly fuzz run run_rom
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rusty::emulator::{self, quirks::Profile};
+
+const PROFILES: [Profile; 4] = [Profile::Modern, Profile::Vip, Profile::Schip, Profile::XoChip];
+const MAX_STEPS: usize = 100_000;
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the configuration, the rest is the program
+    let Some((&config, rom)) = data.split_first() else {
+        return;
+    };
+    let mut emul = emulator::builder()
+        .headless(true)
+        .seed(0)
+        .quirks(PROFILES[(config & 3) as usize])
+        .memory_faults(config & 4 != 0)
+        .build();
+    let mem = emul.get_pgrm_mem();
+    let len = rom.len().min(mem.len());
+    mem[..len].copy_from_slice(&rom[..len]);
+    for _ in 0..MAX_STEPS {
+        match emul.step() {
+            Ok(true) => {}
+            _ => break,
+        }
+    }
+});
@@ -0,0 +1,110 @@
+use crate::cli::BenchArgs;
+use log::error;
+use rusty::emulator;
+use rusty::from_file;
+use std::time::{Duration, Instant};
+
+const OPCODE_CLASSES: [&str; 16] = [
+    "0NNN clear/return",
+    "1NNN jump",
+    "2NNN call",
+    "3XNN skip if eq",
+    "4XNN skip if ne",
+    "5XY0 skip if eq",
+    "6XNN set",
+    "7XNN add",
+    "8XYN logic",
+    "9XY0 skip if ne",
+    "ANNN set index",
+    "BNNN jump offset",
+    "CXNN random",
+    "DXYN draw",
+    "EXNN skip if key",
+    "FXNN misc",
+];
+
+// Peak resident set size as reported by the kernel
+fn peak_memory_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    return line.split_whitespace().nth(1)?.parse().ok();
+}
+
+pub fn run(args: &BenchArgs) {
+    let mut builder = args
+        .memory
+        .apply(emulator::builder())
+        .headless(true)
+        .seed(args.seed)
+        .rng(args.rng.clone())
+        .quirks(args.quirks)
+        .instructions_per_frame(args.instructions_per_frame)
+        .timing(args.timing);
+    if let Some(wait) = args.display_wait {
+        builder = builder.display_wait(wait);
+    }
+    let mut emul = match builder.try_build() {
+        Ok(emul) => emul,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    from_file::read(&args.rom, emul.get_pgrm_mem());
+
+    let mut counts = [0u64; 16];
+    let mut times = [Duration::ZERO; 16];
+    let start = Instant::now();
+    let mut failed = false;
+    while emul.cycles() < args.cycles {
+        let result = if args.opcode_times {
+            let before = Instant::now();
+            let result = emul.step();
+            times[(emul.last_instruction() >> 12) as usize] += before.elapsed();
+            result
+        } else {
+            emul.step()
+        };
+        counts[(emul.last_instruction() >> 12) as usize] += 1;
+        match result {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("Program ended after {} instructions", emul.cycles());
+                break;
+            }
+            Err(e) => {
+                error!("{}", e);
+                failed = true;
+                break;
+            }
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!("{}", args.rom);
+    println!("instructions:  {}", emul.cycles());
+    println!("frames:        {}", emul.frames());
+    println!("elapsed:       {:.3} s", elapsed);
+    println!("instr/s:       {:.0}", emul.cycles() as f64 / elapsed);
+    println!("frames/s:      {:.0}", emul.frames() as f64 / elapsed);
+    match peak_memory_kb() {
+        Some(kb) => println!("peak memory:   {} KiB", kb),
+        None => println!("peak memory:   n/a"),
+    }
+    println!();
+    println!("{:<20} {:>12} {:>12}", "opcode class", "count", "ns/instr");
+    for class in 0..16 {
+        if counts[class] == 0 {
+            continue;
+        }
+        if args.opcode_times {
+            let ns = times[class].as_nanos() as f64 / counts[class] as f64;
+            println!("{:<20} {:>12} {:>12.1}", OPCODE_CLASSES[class], counts[class], ns);
+        } else {
+            println!("{:<20} {:>12} {:>12}", OPCODE_CLASSES[class], counts[class], "-");
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,321 @@
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, Parser, Subcommand};
+use rusty::emulator::{
+    self, audio, export, font, graphics, keymap, orientation, palette, quirks, rng, settings, theme,
+    timing,
+};
+
+#[derive(Parser)]
+#[command(name = "rusty", about = "CHIP-8 emulator", args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run a ROM headless without sleeping and report interpreter throughput
+    Bench(BenchArgs),
+    /// Run a ROM headless for a number of frames and write the final screen
+    Render(RenderArgs),
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// ROM to load, or a directory of ROMs to pick from
+    #[arg(default_value = "prog.ch8")]
+    pub rom: String,
+    /// Stop with an error on memory accesses past the end of ram instead of wrapping around
+    #[arg(long)]
+    pub memory_faults: bool,
+    /// Seed for CXNN, random if not given
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// CXNN generator: xorshift, vip or fixed:<hex bytes separated by commas>
+    #[arg(long, default_value = "xorshift")]
+    pub rng: rng::Mode,
+    /// Quirk profile: modern, vip, schip or xochip
+    #[arg(long, default_value = "modern")]
+    pub quirks: quirks::Profile,
+    /// Make DXYN wait for the vertical blank (true) or not (false), whatever the quirk profile
+    #[arg(long)]
+    pub display_wait: Option<bool>,
+    /// Instructions executed per 60 Hz frame
+    #[arg(long = "ipf", default_value_t = 16)]
+    pub instructions_per_frame: u64,
+    /// Frame length: fixed (--ipf instructions) or vip (machine cycles as on the COSMAC VIP)
+    #[arg(long, default_value = "fixed")]
+    pub timing: timing::Mode,
+    #[command(flatten)]
+    pub memory: MemoryArgs,
+    /// Record keypad changes, the rng and state checkpoints into a movie file
+    #[arg(long, conflicts_with = "play_movie")]
+    pub record_movie: Option<String>,
+    /// Play back a movie file instead of reading the keyboard
+    #[arg(long)]
+    pub play_movie: Option<String>,
+    /// Full screen view of the display, registers, disassembly, call stack and memory, with
+    /// pausing, stepping and editing
+    #[arg(long, conflicts_with = "play_movie")]
+    pub tui: bool,
+    /// Sound: speaker, bell, flash, none or the path of a .wav file to write
+    #[arg(long, default_value = "speaker")]
+    pub audio: audio::Output,
+    /// Buzzer frequency in Hz
+    #[arg(long, default_value_t = 440.0)]
+    pub tone: f64,
+    /// Buzzer volume from 0 to 1, where the output has one
+    #[arg(long, default_value_t = 0.25)]
+    pub volume: f64,
+    /// Draw with text, sixel or kitty graphics, or auto to pick what the terminal supports and
+    /// fall back to text. Graphics use --scale and --palette but not --persistence
+    #[arg(long, default_value = "text")]
+    pub screen: graphics::Mode,
+    /// Terminal theme: emoji, octo, lcd, amber, high-contrast or the path of a toml theme file
+    #[arg(long, default_value = "emoji", value_parser = theme::find)]
+    pub theme: theme::Theme,
+    /// Colours replacing the theme's, unlit first, as 2 or 4 of: default, an ANSI name such as
+    /// red or bright-red, 0-255 from the 256 colour palette or #rrggbb
+    #[arg(long, value_delimiter = ',')]
+    pub colors: Option<Vec<String>>,
+    /// Glyphs replacing the theme's, unlit first, 2 or 4 separated by commas
+    #[arg(long, value_delimiter = ',')]
+    pub glyphs: Option<Vec<String>>,
+    /// Frames a cleared pixel keeps glowing to hide XOR flicker, fading out with 256 colour and
+    /// #rrggbb themes, up to 600
+    #[arg(long, default_value = "0", value_parser = parse_persistence)]
+    pub persistence: u32,
+    /// Keyboard keys for keypad keys 0 to F
+    #[arg(long, default_value = "x123qweasdzc4rfv")]
+    pub keymap: keymap::Keymap,
+    /// What F6 saves: a png, a pbm or a txt grid of # and . to paste as text
+    #[arg(long, default_value = "png")]
+    pub screenshot: export::Format,
+    /// When the game ends, write the --ipf, --quirks, --keymap and --palette given here to the
+    /// ROM's settings file, game.ch8.toml for game.ch8. Settings come from the flags, then that
+    /// file, then the ROM's entry in ~/.config/rusty/roms.toml, then the defaults there. The
+    /// launcher saves nothing by itself: s there saves them for the game just played
+    #[arg(long)]
+    pub save_settings: bool,
+    #[command(flatten)]
+    pub video: VideoArgs,
+    // The settings given as flags rather than left at their defaults
+    #[arg(skip)]
+    pub given: settings::Settings,
+}
+
+impl RunArgs {
+    // The theme with the --colors and --glyphs overrides
+    pub fn theme(&self) -> Result<theme::Theme, String> {
+        let mut theme = self.theme.clone();
+        if let Some(colors) = &self.colors {
+            theme.set_colors(colors)?;
+        }
+        if let Some(glyphs) = &self.glyphs {
+            theme.set_glyphs(glyphs)?;
+        }
+        return Ok(theme);
+    }
+
+    // Notes which settings were given on the command line, so that only those win over the
+    // settings files
+    pub fn read_given(&mut self, matches: &ArgMatches) {
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        self.given = settings::Settings {
+            instructions_per_frame: Some(self.instructions_per_frame)
+                .filter(|_| given("instructions_per_frame")),
+            quirks: Some(self.quirks).filter(|_| given("quirks")),
+            keymap: Some(self.keymap.clone()).filter(|_| given("keymap")),
+            palette: Some(self.video.palette.clone()).filter(|_| given("palette")),
+        };
+    }
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// ROM to load
+    pub rom: String,
+    /// Number of instructions to execute
+    #[arg(long, default_value_t = 10_000_000)]
+    pub cycles: u64,
+    /// Seed for CXNN
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// CXNN generator: xorshift, vip or fixed:<hex bytes separated by commas>
+    #[arg(long, default_value = "xorshift")]
+    pub rng: rng::Mode,
+    /// Quirk profile: modern, vip, schip or xochip
+    #[arg(long, default_value = "modern")]
+    pub quirks: quirks::Profile,
+    /// Make DXYN wait for the vertical blank (true) or not (false), whatever the quirk profile
+    #[arg(long)]
+    pub display_wait: Option<bool>,
+    /// Instructions executed per 60 Hz frame
+    #[arg(long = "ipf", default_value_t = 16)]
+    pub instructions_per_frame: u64,
+    /// Frame length: fixed (--ipf instructions) or vip (machine cycles as on the COSMAC VIP)
+    #[arg(long, default_value = "fixed")]
+    pub timing: timing::Mode,
+    #[command(flatten)]
+    pub memory: MemoryArgs,
+    /// Time every instruction by opcode class (slows down the run itself)
+    #[arg(long)]
+    pub opcode_times: bool,
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// ROM to load
+    pub rom: String,
+    /// Number of frames to run
+    #[arg(long, default_value_t = 600)]
+    pub frames: u64,
+    /// Key script: lines of a frame number followed by the hex digits of the keys held
+    #[arg(long)]
+    pub keys: Option<String>,
+    /// Output file: .png, .pbm or anything else for a text grid
+    #[arg(short, long)]
+    pub output: String,
+    /// Seed for CXNN
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// CXNN generator: xorshift, vip or fixed:<hex bytes separated by commas>
+    #[arg(long, default_value = "xorshift")]
+    pub rng: rng::Mode,
+    /// Quirk profile: modern, vip, schip or xochip
+    #[arg(long, default_value = "modern")]
+    pub quirks: quirks::Profile,
+    /// Make DXYN wait for the vertical blank (true) or not (false), whatever the quirk profile
+    #[arg(long)]
+    pub display_wait: Option<bool>,
+    /// Instructions executed per 60 Hz frame
+    #[arg(long = "ipf", default_value_t = 16)]
+    pub instructions_per_frame: u64,
+    /// Frame length: fixed (--ipf instructions) or vip (machine cycles as on the COSMAC VIP)
+    #[arg(long, default_value = "fixed")]
+    pub timing: timing::Mode,
+    #[command(flatten)]
+    pub memory: MemoryArgs,
+    /// Sound: speaker, bell, flash, none or the path of a .wav file to write
+    #[arg(long, default_value = "none")]
+    pub audio: audio::Output,
+    /// Buzzer frequency in Hz
+    #[arg(long, default_value_t = 440.0)]
+    pub tone: f64,
+    /// Buzzer volume from 0 to 1, where the output has one
+    #[arg(long, default_value_t = 0.25)]
+    pub volume: f64,
+    #[command(flatten)]
+    pub video: VideoArgs,
+}
+
+#[derive(Args)]
+pub struct VideoArgs {
+    /// Record video from the start: a .gif, "-" for raw rgb24 frames on stdout or any other
+    /// path for a raw rgb24 file. F5 starts and stops a recording while running, and F6 saves
+    /// a screenshot
+    #[arg(long = "record")]
+    pub record_video: Option<String>,
+    /// Size of a pixel in png and pbm screenshots and recorded video, from 1 to 64
+    #[arg(long, default_value = "4", value_parser = parse_scale)]
+    pub scale: usize,
+    /// Colours of png screenshots and recorded video as <background>,<foreground> hex
+    #[arg(long, default_value = "000000,ffffff")]
+    pub palette: palette::Palette,
+    /// Degrees to turn the picture clockwise: 0, 90, 180 or 270
+    #[arg(long, default_value = "0", value_parser = orientation::parse_rotation)]
+    pub rotate: u16,
+    /// Mirror the picture left to right, after rotating
+    #[arg(long)]
+    pub flip_horizontal: bool,
+    /// Mirror the picture top to bottom, after rotating
+    #[arg(long)]
+    pub flip_vertical: bool,
+}
+
+impl VideoArgs {
+    pub fn apply(&self, builder: emulator::Builder) -> emulator::Builder {
+        return builder
+            .scale(self.scale)
+            .palette(self.palette.clone())
+            .orientation(orientation::Orientation {
+                rotation: self.rotate,
+                flip_horizontal: self.flip_horizontal,
+                flip_vertical: self.flip_vertical,
+            });
+    }
+}
+
+#[derive(Args)]
+pub struct MemoryArgs {
+    /// RAM size: 4k, 8k, 16k, 32k or 64k
+    #[arg(long, default_value = "8k", value_parser = parse_ram_size)]
+    pub ram_size: usize,
+    /// Where the ROM is loaded and starts, e.g. 0x600 for ETI-660 programs
+    #[arg(long, default_value = "0x200", value_parser = parse_address)]
+    pub load_address: usize,
+    /// Where the font is stored
+    #[arg(long, default_value = "0x50", value_parser = parse_address)]
+    pub font_address: usize,
+    /// Font: vip, eti660, dream6800, schip, octo or the path of a raw font file
+    #[arg(long, default_value = "octo", value_parser = font::find)]
+    pub font: font::Font,
+}
+
+impl MemoryArgs {
+    pub fn apply(&self, builder: emulator::Builder) -> emulator::Builder {
+        return builder
+            .ram_size(self.ram_size)
+            .load_address(self.load_address)
+            .font_address(self.font_address)
+            .font(self.font.clone());
+    }
+}
+
+fn parse_ram_size(text: &str) -> Result<usize, String> {
+    match text.to_lowercase().as_str() {
+        "4k" => return Ok(0x1000),
+        "8k" => return Ok(0x2000),
+        "16k" => return Ok(0x4000),
+        "32k" => return Ok(0x8000),
+        "64k" => return Ok(0x10000),
+        _ => return Err(format!("Unsupported ram size {}", text)),
+    }
+}
+
+const MAX_SCALE: usize = 64;
+
+fn parse_scale(text: &str) -> Result<usize, String> {
+    let scale: usize = text
+        .parse()
+        .map_err(|e| format!("Bad scale {}: {}", text, e))?;
+    if !(1..=MAX_SCALE).contains(&scale) {
+        return Err(format!("The scale goes from 1 to {}, not {}", MAX_SCALE, text));
+    }
+    return Ok(scale);
+}
+
+fn parse_persistence(text: &str) -> Result<u32, String> {
+    let frames: u32 = text
+        .parse()
+        .map_err(|e| format!("Bad persistence {}: {}", text, e))?;
+    if frames > emulator::MAX_PERSISTENCE {
+        return Err(format!(
+            "Persistence goes up to {} frames, not {}",
+            emulator::MAX_PERSISTENCE,
+            text
+        ));
+    }
+    return Ok(frames);
+}
+
+fn parse_address(text: &str) -> Result<usize, String> {
+    let address = usize::from_str_radix(text.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("Bad address {}: {}", text, e))?;
+    if address >= 0x10000 {
+        return Err(format!("Address {} is past the end of a 64k ram", text));
+    }
+    return Ok(address);
+}
@@ -1,65 +1,197 @@
 mod ascii;
+pub mod audio;
+pub mod disassembler;
+pub mod export;
+mod fnv;
+pub mod font;
+pub mod graphics;
 mod instruction;
-use atomic_counter::AtomicCounter;
 use instruction::Instruction;
-use log::{debug, error, trace};
+use log::{error, info, trace, warn};
+use std::fmt;
+use std::hash::Hasher;
 mod keyboard;
+pub mod keymap;
+mod movie;
+pub mod orientation;
+pub mod palette;
+mod phosphor;
+pub mod quirks;
 mod ram;
+pub mod rng;
+pub mod settings;
 mod stack;
+pub mod theme;
 mod timer;
-use rand;
+pub mod timing;
+mod video;
 
-const CLOCK_INTERVAL_US: u64 = 1000;
+const FRAME_INTERVAL_US: u64 = 16667; // 60 hz
+const INSTRUCTIONS_PER_FRAME: u64 = 16;
+const SLOW_MOTION_SPEED: f64 = 0.25;
+const FAST_FORWARD_SPEED: f64 = 4.0;
+const MAX_CALL_DEPTH: usize = 16; // As on SUPER-CHIP; the VIP had room for 12
+pub const MAX_PERSISTENCE: u32 = 600; // Ten seconds
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidInstruction { address: usize, inst: u16 },
+    // Only raised with memory faults enabled; target is the first address past the end of ram
+    MemoryFault { address: usize, target: usize },
+    StackOverflow { address: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidInstruction { address, inst } => {
+                return write!(f, "Invalid instruction {:#06x} at {:#0x}", inst, address)
+            }
+            Error::MemoryFault { address, target } => {
+                return write!(f, "Access to {:#0x} past the end of ram at {:#0x}", target, address)
+            }
+            Error::StackOverflow { address } => {
+                return write!(f, "Call stack overflow at {:#0x}", address)
+            }
+        }
+    }
+}
+
+// Registers, timers and the CXNN generator, for inspecting, saving or setting up the machine from
+// outside
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: usize,
+    pub calls: Vec<usize>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub rng: rng::Rng,
+}
 
 pub struct Emulator {
     display: ascii::Display,
+    screen: Box<dyn ascii::Screen>,
+    redraw: bool, // The display changed since it was last presented
     stack: stack::Stack,
     ram: ram::Ram,
-    sound_timer: timer::ThreadedCounter,
-    delay_timer: timer::ThreadedCounter,
-    keyboard: keyboard::Keyboard,
+    sound_timer: timer::Countdown,
+    delay_timer: timer::Countdown,
+    audio: Box<dyn audio::AudioSink>,
+    tone: audio::Tone,
+    pattern: Option<[u8; 16]>, // XO-CHIP audio, replacing the buzzer once loaded
+    pitch: u8,
+    keyboard: Box<dyn keyboard::Input>,
+    keypad: u16, // Latched at the start of every frame
+    instructions_per_frame: u64,
+    timing: timing::Mode,
+    frame_time: u64, // Instructions or machine cycles spent in the current frame
+    in_frame: bool,
+    profile: quirks::Profile,
+    quirks: quirks::Quirks,
+    memory_faults: bool,
+    rng: rng::Rng,
+    cycles: u64,
+    frames: u64,
+    last_instruction: u16,
+    recorder: Option<movie::Recorder>,
+    player: Option<movie::Player>,
+    video: Option<video::Video>,
+    phosphor: Option<phosphor::Phosphor>,
+    palette: palette::Palette,
+    scale: usize,
+    orientation: orientation::Orientation,
+    screenshot_format: export::Format,
 }
 
 impl Emulator {
-    fn fetch(&mut self) -> u16 {
+    fn fetch(&mut self) -> Result<u16, Error> {
+        let pc = self.ram.address();
+        if !self.ram.contains(pc + 1) {
+            if self.memory_faults {
+                return Err(Error::MemoryFault {
+                    address: pc,
+                    target: pc.max(self.ram.addressable()),
+                });
+            }
+            self.ram.jump_to(self.ram.wrap(pc));
+        }
         let instr = self.ram.fetch();
         self.ram.increment();
-        return instr;
+        return Ok(instr);
+    }
+
+    // Refers to the instruction just fetched
+    fn invalid(&self, inst: u16) -> Error {
+        return Error::InvalidInstruction {
+            address: self.ram.address() - 2,
+            inst,
+        };
+    }
+
+    // Checks an access of len bytes from start made by the instruction just fetched
+    fn check_range(&self, start: usize, len: usize) -> Result<(), Error> {
+        if self.memory_faults && !self.ram.contains(start + len - 1) {
+            return Err(Error::MemoryFault {
+                address: self.ram.address() - 2,
+                target: start.max(self.ram.addressable()),
+            });
+        }
+        return Ok(());
+    }
+
+    fn write_byte(&mut self, address: usize, val: u8) {
+        let address = self.ram.wrap(address);
+        let addr = self.ram.address();
+        self.ram.jump_to(address);
+        self.ram.set_byte(val);
+        self.ram.jump_to(addr);
     }
 
     fn clear_cmd(&mut self, inst: u16) -> bool {
         trace!("clear {:#0x}", inst);
         if inst.jump_addr() == 0x0E0 {
             self.display.clear();
+            self.redraw = true;
         }
         if inst.jump_addr() == 0x0EE {
-            return false;
+            match self.stack.calls.pop() {
+                Some(pos) => self.ram.jump_to(pos),
+                // Returning from the top level ends the program
+                None => return false,
+            }
         }
         return true;
     }
 
-    fn display_cmd(&mut self, inst: u16) {
+    fn display_cmd(&mut self, inst: u16) -> Result<(), Error> {
         trace!("Display cmd! {:#0x}", inst);
         let vx = (self.stack.v[inst.x_register_of() as usize] % (ascii::WIDTH as u8)) as usize;
         let vy = (self.stack.v[inst.y_register_of() as usize] % (ascii::HEIGHT as u8)) as usize;
-        let n = std::cmp::min(
-            inst.fourth_nibble_of() as usize,
-            ascii::HEIGHT as usize - vy,
-        );
+        let n = if self.quirks.wrap_sprites {
+            inst.fourth_nibble_of() as usize
+        } else {
+            std::cmp::min(inst.fourth_nibble_of() as usize, ascii::HEIGHT - vy)
+        };
         let mut zeroed = false;
-
-        let mut i_pos = self.stack.i as usize;
+        if n > 0 {
+            self.check_range(self.stack.i as usize, n)?;
+        }
 
         for i in 0..n {
-            for j in 0..std::cmp::min(8, ascii::WIDTH - vx) {
-                let i_val = self.ram.get(i_pos);
-                let toggle_val = ((i_val >> (7 - j)) & 1) != 0;
-                zeroed |= self.display.xor(toggle_val, vy + i, vx + j);
-            }
-            i_pos += 1;
+            let sprite = (self.ram.get(self.stack.i as usize + i) as ascii::Row) << (ascii::WIDTH - 8);
+            // Pixels past the right edge are either shifted out of the row or rotated to its start
+            let bits = if self.quirks.wrap_sprites {
+                sprite.rotate_right(vx as u32)
+            } else {
+                sprite >> vx
+            };
+            zeroed |= self.display.xor_row((vy + i) % ascii::HEIGHT, bits);
         }
         self.stack.v[0xF] = zeroed as u8;
-        self.display.draw();
+        self.redraw = true;
+        return Ok(());
     }
 
     fn jump_cmd(&mut self, inst: u16) {
@@ -84,71 +216,76 @@ impl Emulator {
         self.stack.i = inst.jump_addr();
     }
 
-    fn subroutine(&mut self, inst: u16) {
+    fn subroutine(&mut self, inst: u16) -> Result<(), Error> {
         trace!("subroutine {:#0x}", inst);
-        let pos = self.ram.address();
+        if self.stack.calls.len() >= MAX_CALL_DEPTH {
+            return Err(Error::StackOverflow {
+                address: self.ram.address() - 2,
+            });
+        }
+        self.stack.calls.push(self.ram.address());
         self.ram.jump_to(inst.jump_addr() as usize);
-        self.start_loop();
-        self.ram.jump_to(pos);
+        return Ok(());
     }
 
     fn logic_cmd(&mut self, inst: u16) {
+        let x = inst.x_register_of() as usize;
+        let y = inst.y_register_of() as usize;
+        // Flags are written last, so that they win when X is F
         match inst.fourth_nibble_of() {
             0 => {
-                self.stack.v[inst.x_register_of() as usize] =
-                    self.stack.v[inst.y_register_of() as usize];
+                self.stack.v[x] = self.stack.v[y];
             }
             1 => {
-                self.stack.v[inst.x_register_of() as usize] |=
-                    self.stack.v[inst.y_register_of() as usize];
+                self.stack.v[x] |= self.stack.v[y];
+                if self.quirks.vf_reset {
+                    self.stack.v[0xF] = 0;
+                }
             }
             2 => {
-                self.stack.v[inst.x_register_of() as usize] &=
-                    self.stack.v[inst.y_register_of() as usize];
+                self.stack.v[x] &= self.stack.v[y];
+                if self.quirks.vf_reset {
+                    self.stack.v[0xF] = 0;
+                }
             }
             3 => {
-                self.stack.v[inst.x_register_of() as usize] ^=
-                    self.stack.v[inst.y_register_of() as usize];
+                self.stack.v[x] ^= self.stack.v[y];
+                if self.quirks.vf_reset {
+                    self.stack.v[0xF] = 0;
+                }
             }
             4 => {
-                let sum = self.stack.v[inst.x_register_of() as usize] as u16
-                    + self.stack.v[inst.y_register_of() as usize] as u16;
-                self.stack.v[0xF] = if sum > 255 { 1 } else { 0 };
-                self.stack.v[inst.x_register_of() as usize] = (sum & 0xFF) as u8;
+                let (sum, carry) = self.stack.v[x].overflowing_add(self.stack.v[y]);
+                self.stack.v[x] = sum;
+                self.stack.v[0xF] = carry as u8;
             }
             5 => {
-                let diff = self.stack.v[inst.x_register_of() as usize] as i16
-                    - self.stack.v[inst.y_register_of() as usize] as i16;
-                self.stack.v[0xF] = if diff > 0 { 1 } else { 0 };
-                self.stack.v[inst.x_register_of() as usize] = (diff & 0xFF) as u8;
+                let (diff, borrow) = self.stack.v[x].overflowing_sub(self.stack.v[y]);
+                self.stack.v[x] = diff;
+                self.stack.v[0xF] = !borrow as u8;
             }
             7 => {
-                let diff = self.stack.v[inst.y_register_of() as usize] as i16
-                    - self.stack.v[inst.x_register_of() as usize] as i16;
-                self.stack.v[0xF] = if diff > 0 { 1 } else { 0 };
-                self.stack.v[inst.x_register_of() as usize] = (diff & 0xFF) as u8;
+                let (diff, borrow) = self.stack.v[y].overflowing_sub(self.stack.v[x]);
+                self.stack.v[x] = diff;
+                self.stack.v[0xF] = !borrow as u8;
             }
             6 => {
-                // Old
-                //self.stack.v[inst.x_register_of() as usize] =
-                //    self.stack.v[inst.y_register_of() as usize] >> 1;
-                //self.stack.v[0xF] = self.stack.v[inst.y_register_of() as usize] & 1;
-                // New
-                self.stack.v[0xF] = self.stack.v[inst.x_register_of() as usize] & 1;
-                self.stack.v[inst.x_register_of() as usize] =
-                    self.stack.v[inst.x_register_of() as usize] >> 1;
-                debug!("Warning: Ambiguous instruction {:#0x}", inst);
+                let val = if self.quirks.shift_uses_vy {
+                    self.stack.v[y]
+                } else {
+                    self.stack.v[x]
+                };
+                self.stack.v[x] = val >> 1;
+                self.stack.v[0xF] = val & 1;
             }
             0xE => {
-                // Old
-                // self.stack.v[inst.x_register_of() as usize] =
-                //     self.stack.v[inst.y_register_of() as usize] << 1;
-                // self.stack.v[0xF] = self.stack.v[inst.y_register_of() as usize] >> 7;
-                // New
-                self.stack.v[0xF] = self.stack.v[inst.x_register_of() as usize] >> 7;
-                self.stack.v[inst.x_register_of() as usize] =
-                    self.stack.v[inst.x_register_of() as usize] << 1;
-                debug!("Warning: Ambiguous instruction {:#0x}", inst);
+                let val = if self.quirks.shift_uses_vy {
+                    self.stack.v[y]
+                } else {
+                    self.stack.v[x]
+                };
+                self.stack.v[x] = val << 1;
+                self.stack.v[0xF] = val >> 7;
             }
             _ => {
                 error!("Bad logic_cmd {:#0x}", inst);
@@ -157,16 +294,19 @@ impl Emulator {
     }
 
     fn jump_offset_cmd(&mut self, inst: u16) {
-        debug!("jump_offset_cmd has ambiguous definitions {:#0x}", inst);
-        self.ram
-            .jump_to(inst.jump_addr() as usize + self.stack.v[0] as usize);
+        let offset = if self.quirks.jump_uses_vx {
+            self.stack.v[inst.x_register_of() as usize]
+        } else {
+            self.stack.v[0]
+        };
+        self.ram.jump_to(inst.jump_addr() as usize + offset as usize);
     }
 
     fn random(&mut self, inst: u16) {
-        self.stack.v[inst.x_register_of() as usize] = rand::random::<u8>() & inst.second_byte_of();
+        self.stack.v[inst.x_register_of() as usize] = self.rng.next_byte() & inst.second_byte_of();
     }
 
-    fn skip_cmd(&mut self, inst: u16) {
+    fn skip_cmd(&mut self, inst: u16) -> Result<(), Error> {
         match inst.instruction_of() {
             0x3 => {
                 if (self.stack.v[inst.x_register_of() as usize]) == inst.second_byte_of() {
@@ -192,167 +332,809 @@ impl Emulator {
                     self.ram.increment();
                 }
             }
-            _ => {
-                error!("Invalid skip");
-                std::process::exit(1);
-            }
+            _ => return Err(self.invalid(inst)),
         }
+        return Ok(());
+    }
+
+    fn is_pressed(&self, key: u8) -> bool {
+        trace!("IsPressed {}", key);
+        return key < 16 && (self.keypad >> key) & 1 != 0;
     }
 
-    fn skip_if_cmd(&mut self, inst: u16) {
+    fn skip_if_cmd(&mut self, inst: u16) -> Result<(), Error> {
         match inst.second_byte_of() {
             0x9E => {
-                if self
-                    .keyboard
-                    .is_pressed(self.stack.v[inst.x_register_of() as usize] as i32)
-                {
+                if self.is_pressed(self.stack.v[inst.x_register_of() as usize]) {
                     self.ram.increment();
                 }
             }
             0xA1 => {
-                if !self
-                    .keyboard
-                    .is_pressed(self.stack.v[inst.x_register_of() as usize] as i32)
-                {
+                if !self.is_pressed(self.stack.v[inst.x_register_of() as usize]) {
                     self.ram.increment();
                 }
             }
-            _ => {
-                error!("Invalid skip_if");
-                std::process::exit(1);
-            }
+            _ => return Err(self.invalid(inst)),
         }
+        return Ok(());
     }
 
     fn wait_keypress(&mut self, inst: u16) {
-        let val = self.keyboard.get_press();
-        if val == -1 {
+        if self.keypad == 0 {
             self.ram.decrement();
             return;
         }
-        self.stack.v[inst.x_register_of() as usize] = val as u8;
+        self.stack.v[inst.x_register_of() as usize] = self.keypad.trailing_zeros() as u8;
     }
 
-    fn bloated_cmd(&mut self, inst: u16) {
+    fn bloated_cmd(&mut self, inst: u16) -> Result<(), Error> {
         match inst.second_byte_of() {
             0x07 => {
-                let val: u8 = 255 - (*self.delay_timer.counter).get() as u8;
-                self.stack.v[inst.x_register_of() as usize] = val;
+                self.stack.v[inst.x_register_of() as usize] = self.delay_timer.value;
             }
             0x15 => {
-                let val = 255 - self.stack.v[inst.x_register_of() as usize] as usize;
-                (*self.delay_timer.counter).reset();
-                // We are simply hoping the timer will not have time to increment :P
-                // Locks would be required for better thread handling
-                (*self.delay_timer.counter).add(val);
+                self.delay_timer.value = self.stack.v[inst.x_register_of() as usize];
             }
             0x18 => {
-                let val = 255 - self.stack.v[inst.x_register_of() as usize] as usize;
-                (*self.sound_timer.counter).reset();
-                // We are simply hoping the timer will not have time to increment :P
-                // Locks would be required for better thread handling
-                (*self.sound_timer.counter).add(val);
+                self.sound_timer.value = self.stack.v[inst.x_register_of() as usize];
             }
             0x1E => {
-                let sum = self.stack.i + self.stack.v[inst.x_register_of() as usize] as u16;
-                // Instructions unclear; should the bit only be set on overflow? Or never at all?
-                self.stack.v[0xF] = (sum > 0xFFF) as u8;
-                self.stack.i = sum & 0xFFF;
+                let sum = self.stack.i.wrapping_add(self.stack.v[inst.x_register_of() as usize] as u16);
+                if self.quirks.index_overflow_sets_vf {
+                    self.stack.v[0xF] = (sum > 0xFFF) as u8;
+                }
+                self.stack.i = sum & self.quirks.address_mask() as u16;
             }
             0x0A => {
                 self.wait_keypress(inst);
             }
+            0x02 if inst == 0xF002 && self.quirks.audio_patterns => {
+                self.check_range(self.stack.i as usize, 16)?;
+                let mut bits = [0; 16];
+                for (i, byte) in bits.iter_mut().enumerate() {
+                    *byte = self.ram.get(self.stack.i as usize + i);
+                }
+                self.pattern = Some(bits);
+            }
+            0x3A if self.quirks.audio_patterns => {
+                self.pitch = self.stack.v[inst.x_register_of() as usize];
+            }
             0x29 => {
-                // Whats the encoding here? Is 0 the first letter?
-                self.stack.i =
-                    (ram::FONT_POS as u16) + self.stack.v[inst.x_register_of() as usize] as u16;
+                let glyph = (self.stack.v[inst.x_register_of() as usize] & 0xF) as usize;
+                self.stack.i = (self.ram.font_address() + font::SMALL_GLYPH * glyph) as u16;
+            }
+            0x30 => {
+                let glyph = (self.stack.v[inst.x_register_of() as usize] & 0xF) as usize;
+                self.stack.i = (self.ram.big_font_address() + font::BIG_GLYPH * glyph) as u16;
             }
             0x33 => {
+                self.check_range(self.stack.i as usize, 3)?;
                 let mut val = self.stack.v[inst.x_register_of() as usize];
-                let addr = self.ram.address();
                 for i in 0..3 {
-                    self.ram.jump_to(self.stack.i as usize + (2 - i) as usize);
-                    self.ram.set_byte(val % 10);
+                    self.write_byte(self.stack.i as usize + (2 - i) as usize, val % 10);
                     val /= 10;
                 }
-                self.ram.jump_to(addr);
             }
             0x55 => {
-                let addr = self.ram.address();
+                self.check_range(self.stack.i as usize, inst.x_register_of() as usize + 1)?;
                 for i in 0..(inst.x_register_of() + 1) {
-                    self.ram.jump_to(self.stack.i as usize + i as usize);
-                    self.ram.set_byte(self.stack.v[i as usize]);
+                    self.write_byte(self.stack.i as usize + i as usize, self.stack.v[i as usize]);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.stack.i = self.stack.i.wrapping_add(inst.x_register_of() + 1);
                 }
-                self.ram.jump_to(addr); // New behaviour: jump back;
             }
             0x65 => {
+                self.check_range(self.stack.i as usize, inst.x_register_of() as usize + 1)?;
                 for i in 0..(inst.x_register_of() + 1) {
                     self.stack.v[i as usize] = self.ram.get(self.stack.i as usize + i as usize);
                 }
+                if self.quirks.load_store_increments_i {
+                    self.stack.i = self.stack.i.wrapping_add(inst.x_register_of() + 1);
+                }
             }
-            _ => {
-                error!("Invalid f cmd");
-                std::process::exit(1);
-            }
+            _ => return Err(self.invalid(inst)),
         }
+        return Ok(());
     }
 
-    fn execute(&mut self, inst: u16) -> bool {
+    fn execute(&mut self, inst: u16) -> Result<bool, Error> {
         match inst.instruction_of() {
-            0x0 => return self.clear_cmd(inst),
+            0x0 => return Ok(self.clear_cmd(inst)),
             0x1 => self.jump_cmd(inst),
-            0x2 => self.subroutine(inst),
-            0x3 | 0x4 | 0x5 | 0x9 => self.skip_cmd(inst),
+            0x2 => self.subroutine(inst)?,
+            0x3 | 0x4 | 0x5 | 0x9 => self.skip_cmd(inst)?,
             0x6 => self.register_set(inst),
             0x7 => self.register_add(inst),
             0x8 => self.logic_cmd(inst),
             0xA => self.index_set(inst),
             0xB => self.jump_offset_cmd(inst),
             0xC => self.random(inst),
-            0xD => self.display_cmd(inst),
-            0xE => self.skip_if_cmd(inst),
-            0xF => self.bloated_cmd(inst),
-            _ => {
-                error!("Invalid instruction {:#0x}", inst);
-                std::process::exit(1);
-            }
+            0xD => self.display_cmd(inst)?,
+            0xE => self.skip_if_cmd(inst)?,
+            0xF => self.bloated_cmd(inst)?,
+            _ => return Err(self.invalid(inst)),
         }
-        return true;
+        return Ok(true);
     }
 
-    fn loop_run(&mut self) -> bool {
-        let val = self.fetch();
-        return self.execute(val);
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = fnv::create();
+        hasher.write(&self.stack.v);
+        hasher.write(&self.stack.i.to_le_bytes());
+        hasher.write(&(self.ram.address() as u64).to_le_bytes());
+        for call in self.stack.calls.iter() {
+            hasher.write(&(*call as u64).to_le_bytes());
+        }
+        hasher.write(self.ram.bytes());
+        for row in self.display.buffer.iter() {
+            hasher.write(&row.to_le_bytes());
+        }
+        hasher.write(&[self.delay_timer.value, self.sound_timer.value, self.pitch]);
+        if let Some(bits) = &self.pattern {
+            hasher.write(bits);
+        }
+        hasher.write(self.rng.to_string().as_bytes());
+        return hasher.finish();
     }
 
-    fn start_loop(&mut self) {
-        let mut timer = timer::Timer {
-            interval: std::time::Duration::from_micros(CLOCK_INTERVAL_US),
-            action: || self.loop_run(),
+    pub fn screen_hash(&self) -> u64 {
+        let mut hasher = fnv::create();
+        for row in self.display.buffer.iter() {
+            hasher.write(&row.to_le_bytes());
+        }
+        return hasher.finish();
+    }
+
+    fn start_frame(&mut self) {
+        self.keypad = self.keyboard.poll(self.frames);
+        if self.frames.is_multiple_of(movie::CHECKPOINT_INTERVAL)
+            && (self.recorder.is_some() || self.player.is_some())
+        {
+            let hash = self.state_hash();
+            if let Some(recorder) = &mut self.recorder {
+                recorder.check(self.frames, hash);
+            }
+            if let Some(player) = &mut self.player {
+                player.check(self.frames, hash);
+            }
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.keys(self.frames, self.keypad);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        // Sound plays for as many frames as the sound timer was set to
+        let sound = match self.pattern {
+            _ if self.sound_timer.value == 0 => None,
+            Some(bits) => Some(audio::Sound::Pattern {
+                bits,
+                rate: audio::pattern_rate(self.pitch),
+                volume: self.tone.volume,
+            }),
+            None => Some(audio::Sound::Tone(self.tone)),
         };
-        timer.run();
+        self.audio.frame(sound);
+        self.delay_timer.tick();
+        self.sound_timer.tick();
+        if let Some(phosphor) = &mut self.phosphor {
+            if phosphor.update(&self.display) {
+                self.redraw = true;
+            }
+        }
+        if self.redraw {
+            self.screen.present(&self.display, self.phosphor.as_ref());
+            self.redraw = false;
+        }
+        if let Some(video) = &mut self.video {
+            video.frame(&self.display);
+        }
+        self.frames += 1;
+    }
+
+    fn frame_budget(&self) -> u64 {
+        match self.timing {
+            timing::Mode::Fixed => return self.instructions_per_frame,
+            timing::Mode::Vip => return timing::VIP_FRAME_BUDGET,
+        }
+    }
+
+    fn instruction_time(&self, inst: u16, vx: u8, pc: usize) -> u64 {
+        match self.timing {
+            timing::Mode::Fixed => return 1,
+            timing::Mode::Vip => {
+                let skip = matches!(inst.instruction_of(), 0x3 | 0x4 | 0x5 | 0x9 | 0xE);
+                let skipped = skip && self.ram.address() == pc + 4;
+                return timing::vip_cycles(inst, vx, skipped);
+            }
+        }
+    }
+
+    // Executes a single instruction. Returns false once the program has ended
+    pub fn step(&mut self) -> Result<bool, Error> {
+        if !self.in_frame {
+            if let Some(player) = &self.player {
+                if player.finished(self.frames) {
+                    return Ok(false);
+                }
+            }
+            self.start_frame();
+            self.in_frame = true;
+        }
+        let pc = self.ram.address();
+        let val = self.fetch()?;
+        self.rng.clock();
+        self.last_instruction = val;
+        let vx = self.stack.v[val.x_register_of() as usize];
+        let running = self.execute(val)?;
+        self.cycles += 1;
+        let time = self.instruction_time(val, vx, pc);
+        if self.quirks.display_wait && val.instruction_of() == 0xD {
+            // The draw only starts at the vertical blank: the frame ends, and on the VIP the
+            // drawing itself is paid for in the next one
+            self.frame_time = self.frame_budget();
+            if self.timing == timing::Mode::Vip {
+                self.frame_time += time;
+            }
+        } else {
+            self.frame_time += time;
+        }
+        if self.frame_time >= self.frame_budget() {
+            // An instruction running past the end of the frame takes the overrun from the next
+            self.frame_time -= self.frame_budget();
+            self.in_frame = false;
+            self.end_frame();
+        }
+        return Ok(running);
+    }
+
+    // Runs the rest of the current frame. Returns false once the program has ended
+    pub fn run_frame(&mut self) -> Result<bool, Error> {
+        loop {
+            if !self.step()? {
+                return Ok(false);
+            }
+            if !self.in_frame {
+                return Ok(true);
+            }
+        }
+    }
+
+    // Runs without sleeping until the given number of frames have passed or the program ends
+    pub fn run_frames(&mut self, frames: u64) -> Result<(), Error> {
+        while self.frames < frames {
+            if !self.step()? {
+                break;
+            }
+        }
+        return Ok(());
+    }
+
+    // One frame per 60th of a second, with the keyboard's pause, frame advance, slow motion and
+    // fast forward controls
+    fn start_loop(&mut self) -> Result<(), Error> {
+        let interval = std::time::Duration::from_micros(FRAME_INTERVAL_US);
+        let mut scheduler = timer::create_scheduler(interval);
+        let mut paused = false;
+        loop {
+            let controls = self.keyboard.controls();
+            if controls.quit {
+                return Ok(());
+            }
+            if controls.record {
+                self.toggle_video();
+            }
+            if controls.screenshot {
+                if let Err(e) = self.screenshot() {
+                    error!("{}", e);
+                }
+            }
+            if controls.pause {
+                paused = !paused;
+                info!("{} at frame {}", if paused { "Paused" } else { "Resumed" }, self.frames);
+            }
+            if (!paused || controls.advance) && !self.run_frame()? {
+                return Ok(());
+            }
+            let speed = if controls.fast_forward {
+                FAST_FORWARD_SPEED
+            } else if controls.slow_motion {
+                SLOW_MOTION_SPEED
+            } else {
+                1.0
+            };
+            scheduler.wait(speed);
+        }
     }
 
     pub fn get_pgrm_mem(&mut self) -> &mut [u8] {
         return self.ram.get_pgrm_mem();
     }
 
-    pub fn run(&mut self) {
-        self.display.draw();
-        self.start_loop();
-        // This will stop the threads
-        (*self.sound_timer.counter).add(256);
-        (*self.delay_timer.counter).add(256);
+    pub fn cycles(&self) -> u64 {
+        return self.cycles;
+    }
+
+    pub fn frames(&self) -> u64 {
+        return self.frames;
+    }
+
+    pub fn last_instruction(&self) -> u16 {
+        return self.last_instruction;
+    }
+
+    pub fn state(&self) -> State {
+        return State {
+            v: self.stack.v,
+            i: self.stack.i,
+            pc: self.ram.address(),
+            calls: self.stack.calls.clone(),
+            delay_timer: self.delay_timer.value,
+            sound_timer: self.sound_timer.value,
+            rng: self.rng.clone(),
+        };
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.stack.v = state.v;
+        self.stack.i = state.i;
+        self.ram.jump_to(state.pc);
+        self.stack.calls = state.calls;
+        self.delay_timer.value = state.delay_timer;
+        self.sound_timer.value = state.sound_timer;
+        self.rng = state.rng;
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        return self.ram.bytes();
+    }
+
+    // How many addresses I and PC can reach, 0x1000 with 12 bit addresses
+    pub fn addressable(&self) -> usize {
+        return self.ram.addressable();
+    }
+
+    // Copies bytes into ram, e.g. code for a test
+    pub fn load(&mut self, address: usize, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.write_byte(address + i, *byte);
+        }
+    }
+
+    // How the screen is turned for presentation
+    pub fn orientation(&self) -> orientation::Orientation {
+        return self.orientation;
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        return self.display.get(y, x);
+    }
+
+    // Replaces the keyboard with keys that stay held
+    pub fn hold_keys(&mut self, keys: u16) {
+        self.keyboard = Box::new(keyboard::create_scripted(vec![(0, keys)]));
+        self.keypad = keys;
+    }
+
+    // The glyphs FX29 and FX30 point at, as loaded into ram
+    fn font_hash(&self) -> u64 {
+        let start = self.ram.font_address();
+        let mut hasher = fnv::create();
+        for address in start..start + font::SMALL_BYTES + font::BIG_BYTES {
+            hasher.write_u8(self.ram.bytes()[self.ram.wrap(address)]);
+        }
+        return hasher.finish();
+    }
+
+    // Starts writing a movie of the session. Call after loading the program, before running it
+    pub fn record_movie(&mut self, path: &str) -> Result<(), String> {
+        let rom = movie::rom_hash(self.ram.get_pgrm_mem());
+        let mut recorder = movie::create_recorder(
+            path,
+            rom,
+            self.ram.layout(),
+            self.instructions_per_frame,
+            self.timing,
+            self.profile,
+            &self.rng,
+        )?;
+        recorder.font(self.font_hash());
+        recorder.memory_faults(self.memory_faults);
+        if self.quirks.display_wait != self.profile.quirks().display_wait {
+            recorder.display_wait(self.quirks.display_wait);
+        }
+        self.recorder = Some(recorder);
+        return Ok(());
+    }
+
+    // Plays the sound timer's tone on the speaker, the terminal or into a wav file
+    pub fn output_audio(&mut self, output: &audio::Output) -> Result<(), String> {
+        self.audio = audio::create(output)?;
+        return Ok(());
+    }
+
+    // Replaces the keyboard with the keys of a recorded movie. Call after loading the program
+    pub fn play_movie(&mut self, path: &str) -> Result<(), String> {
+        let movie = movie::load(path)?;
+        if movie.rom != movie::rom_hash(self.ram.get_pgrm_mem()) {
+            return Err(format!("{} was recorded with a different program", path));
+        }
+        if movie.layout.is_some_and(|layout| layout != self.ram.layout()) {
+            return Err(format!("{} was recorded with a different memory layout", path));
+        }
+        if movie.font.is_some_and(|font| font != self.font_hash()) {
+            return Err(format!("{} was recorded with a different font", path));
+        }
+        if let Some(faults) = movie.memory_faults {
+            self.memory_faults = faults;
+        }
+        self.rng = movie.rng.clone();
+        if let Some(instructions_per_frame) = movie.instructions_per_frame {
+            self.instructions_per_frame = instructions_per_frame.max(1);
+        }
+        if let Some(timing) = movie.timing {
+            self.timing = timing;
+        }
+        self.profile = movie.profile;
+        self.quirks = movie.profile.quirks();
+        if let Some(wait) = movie.display_wait {
+            self.quirks.display_wait = wait;
+        }
+        self.ram.set_address_mask(self.quirks.address_mask());
+        self.player = Some(movie::create_player(&movie));
+        self.keyboard = Box::new(keyboard::create_scripted(movie.keys));
+        return Ok(());
+    }
+
+    // Holds the keys of a script from keyboard::load_script instead of reading the keyboard
+    pub fn script_keys(&mut self, path: &str) -> Result<(), String> {
+        self.keyboard = Box::new(keyboard::create_scripted(keyboard::load_script(path)?));
+        return Ok(());
+    }
+
+    // Records every frame from now on as a gif, or as raw rgb24 video for any other path
+    // or stdout for "-"
+    pub fn record_video(&mut self, path: &str) -> Result<(), String> {
+        self.video = Some(video::create(
+            path,
+            self.orientation,
+            self.scale,
+            self.palette.clone(),
+        )?);
+        return Ok(());
+    }
+
+    pub fn stop_video(&mut self) {
+        self.video = None;
+    }
+
+    // The recording hotkey: stops the current recording, or starts one named after the time
+    fn toggle_video(&mut self) {
+        if self.video.is_some() {
+            self.stop_video();
+            return;
+        }
+        if let Err(e) = self.record_video(&timestamped("gif")) {
+            error!("{}", e);
+        }
+    }
+
+    // Writes the screen as png, pbm or, for any other extension, a text grid
+    pub fn save_screen(&self, path: &str) -> Result<(), String> {
+        return export::save(
+            &self.display,
+            path,
+            &self.orientation,
+            self.scale,
+            &self.palette,
+        );
+    }
+
+    // The screen as drawn by a terminal image protocol
+    pub fn encode_screen(&self, protocol: graphics::Protocol) -> String {
+        return graphics::encode(
+            protocol,
+            &self.display,
+            &self.orientation,
+            self.scale,
+            &self.palette,
+        );
+    }
+
+    // Saves the screen to a new file in the screenshot format. Returns its path
+    pub fn screenshot(&self) -> Result<String, String> {
+        let path = timestamped(self.screenshot_format.extension());
+        self.save_screen(&path)?;
+        info!("Saved the screen to {}", path);
+        return Ok(path);
+    }
+
+    pub fn run(&mut self) -> Result<(), Error> {
+        self.screen.present(&self.display, self.phosphor.as_ref());
+        let result = self.start_loop();
+        if self.redraw {
+            // Whatever was drawn during the last, unfinished frame
+            self.screen.present(&self.display, self.phosphor.as_ref());
+        }
+        self.finish();
+        return result;
+    }
+
+    // Ends the movie being recorded. run does this itself, callers running frame by frame do it
+    // when they stop
+    pub fn finish(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.end(self.frames);
+        }
+    }
+}
+
+pub struct Builder {
+    headless: bool,
+    memory_faults: bool,
+    instructions_per_frame: u64,
+    timing: timing::Mode,
+    layout: ram::Layout,
+    font: font::Font,
+    tone: audio::Tone,
+    palette: palette::Palette,
+    scale: usize,
+    screenshot_format: export::Format,
+    theme: theme::Theme,
+    keymap: keymap::Keymap,
+    persistence: u32,
+    orientation: orientation::Orientation,
+    screen: graphics::Mode,
+    seed: Option<u64>,
+    rng: rng::Mode,
+    profile: quirks::Profile,
+    display_wait: Option<bool>,
+}
+
+impl Builder {
+    // No terminal output and no keyboard; nothing is ever pressed
+    pub fn headless(mut self, headless: bool) -> Builder {
+        self.headless = headless;
+        return self;
+    }
+
+    pub fn seed(mut self, seed: u64) -> Builder {
+        self.seed = Some(seed);
+        return self;
+    }
+
+    pub fn rng(mut self, mode: rng::Mode) -> Builder {
+        self.rng = mode;
+        return self;
+    }
+
+    pub fn quirks(mut self, profile: quirks::Profile) -> Builder {
+        self.profile = profile;
+        return self;
+    }
+
+    // DXYN waits for the vertical blank, or doesn't, whatever the quirk profile says
+    pub fn display_wait(mut self, wait: bool) -> Builder {
+        self.display_wait = Some(wait);
+        return self;
+    }
+
+    // Accesses past the end of ram stop the program with an error instead of wrapping around
+    pub fn memory_faults(mut self, memory_faults: bool) -> Builder {
+        self.memory_faults = memory_faults;
+        return self;
+    }
+
+    // 4K as on the VIP up to 64K for XO-CHIP, rounded up to a power of two. 8K by default
+    pub fn ram_size(mut self, size: usize) -> Builder {
+        self.layout.size = size;
+        return self;
+    }
+
+    // Where the program is loaded and starts, 0x200 by default
+    pub fn load_address(mut self, address: usize) -> Builder {
+        self.layout.load_address = address;
+        return self;
+    }
+
+    pub fn font_address(mut self, address: usize) -> Builder {
+        self.layout.font_address = address;
+        return self;
+    }
+
+    pub fn font(mut self, font: font::Font) -> Builder {
+        self.font = font;
+        return self;
+    }
+
+    // 16 by default. With VIP timing the count depends on the program instead: about 50 for plain
+    // register arithmetic, 25 to 30 for a loop that draws a sprite and about 10 for one that mostly
+    // draws
+    pub fn instructions_per_frame(mut self, instructions: u64) -> Builder {
+        self.instructions_per_frame = instructions.max(1);
+        return self;
+    }
+
+    // Frames of a fixed number of instructions, or as long as on the VIP
+    pub fn timing(mut self, mode: timing::Mode) -> Builder {
+        self.timing = mode;
+        return self;
+    }
+
+    // Frequency and volume of the buzzer
+    pub fn tone(mut self, tone: audio::Tone) -> Builder {
+        self.tone = tone;
+        return self;
+    }
+
+    // Colours of screenshots and recorded video
+    pub fn palette(mut self, palette: palette::Palette) -> Builder {
+        self.palette = palette;
+        return self;
+    }
+
+    // How the terminal draws pixels
+    pub fn theme(mut self, theme: theme::Theme) -> Builder {
+        self.theme = theme;
+        return self;
+    }
+
+    // The keyboard keys read as keypad keys 0 to F
+    pub fn keymap(mut self, keymap: keymap::Keymap) -> Builder {
+        self.keymap = keymap;
+        return self;
+    }
+
+    // Frames a cleared pixel keeps glowing on the terminal, 0 for none, up to MAX_PERSISTENCE
+    pub fn persistence(mut self, frames: u32) -> Builder {
+        self.persistence = frames.min(MAX_PERSISTENCE);
+        return self;
+    }
+
+    // Text, or pixels through a terminal image protocol drawn with the scale and palette
+    pub fn screen(mut self, mode: graphics::Mode) -> Builder {
+        self.screen = mode;
+        return self;
+    }
+
+    // Rotation and flips of the terminal, screenshots and recorded video
+    pub fn orientation(mut self, orientation: orientation::Orientation) -> Builder {
+        self.orientation = orientation;
+        return self;
+    }
+
+    // Pixel size of screenshots and recorded video
+    pub fn scale(mut self, scale: usize) -> Builder {
+        self.scale = scale.max(1);
+        return self;
+    }
+
+    // What the screenshot hotkey saves
+    pub fn screenshot_format(mut self, format: export::Format) -> Builder {
+        self.screenshot_format = format;
+        return self;
+    }
+
+    // Panics where try_build returns an error
+    pub fn build(self) -> Emulator {
+        return self.try_build().unwrap();
+    }
+
+    // Fails if the font doesn't fit in ram below the program
+    pub fn try_build(self) -> Result<Emulator, String> {
+        let seed = self.seed.unwrap_or_else(rand::random::<u64>);
+        let mut quirks = self.profile.quirks();
+        if let Some(wait) = self.display_wait {
+            quirks.display_wait = wait;
+        }
+        let ram = ram::create_ram(self.layout, &self.font, quirks.address_mask())?;
+        info!("Random seed {}", seed);
+        // Only the text screen shows the phosphor's glow
+        let mut persistence = if self.headless { 0 } else { self.persistence };
+        let screen: Box<dyn ascii::Screen> = if self.headless {
+            Box::new(ascii::NullScreen {})
+        } else if let Some(protocol) = graphics::choose(self.screen) {
+            if persistence > 0 {
+                warn!("{:?} graphics don't show persistence, ignoring it", protocol);
+                persistence = 0;
+            }
+            Box::new(graphics::create_graphics(
+                protocol,
+                self.orientation,
+                self.scale,
+                self.palette.clone(),
+            ))
+        } else {
+            Box::new(ascii::Terminal {
+                theme: self.theme,
+                turn: self.orientation,
+            })
+        };
+        let keyboard: Box<dyn keyboard::Input> = if self.headless {
+            Box::new(keyboard::NullInput {})
+        } else {
+            Box::new(keyboard::create(&self.keymap))
+        };
+        return Ok(Emulator {
+            display: ascii::create_display(),
+            screen,
+            redraw: false,
+            stack: stack::Stack {
+                i: 0,
+                v: [0; 16],
+                calls: Vec::new(),
+            },
+            ram,
+            memory_faults: self.memory_faults,
+            sound_timer: timer::Countdown { value: 0 },
+            delay_timer: timer::Countdown { value: 0 },
+            audio: Box::new(audio::NullAudio {}),
+            tone: self.tone,
+            pattern: None,
+            pitch: 64,
+            keyboard,
+            keypad: 0,
+            instructions_per_frame: self.instructions_per_frame,
+            timing: self.timing,
+            frame_time: 0,
+            in_frame: false,
+            profile: self.profile,
+            quirks,
+            rng: rng::create(self.rng, seed),
+            cycles: 0,
+            frames: 0,
+            last_instruction: 0,
+            recorder: None,
+            player: None,
+            video: None,
+            phosphor: if persistence > 0 {
+                Some(phosphor::create_phosphor(persistence))
+            } else {
+                None
+            },
+            palette: self.palette,
+            scale: self.scale,
+            orientation: self.orientation,
+            screenshot_format: self.screenshot_format,
+        });
+    }
+}
+
+// A file name for hotkey captures, e.g. rusty-1700000000.png, or rusty-1700000000-2.png for the
+// next one in the same second, so that no capture overwrites another
+fn timestamped(extension: &str) -> String {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+    let seconds = now.map(|now| now.as_secs()).unwrap_or(0);
+    let mut path = format!("rusty-{}.{}", seconds, extension);
+    let mut count = 1;
+    while std::path::Path::new(&path).exists() {
+        count += 1;
+        path = format!("rusty-{}-{}.{}", seconds, count, extension);
     }
+    return path;
 }
 
-pub fn create_emulator() -> Emulator {
-    return Emulator {
-        display: ascii::create_display(),
-        stack: stack::Stack { i: 0, v: [0; 16] },
-        ram: ram::create_ram(),
-        sound_timer: timer::create_threaded_counter(std::time::Duration::from_micros(16667)), // 60 hz
-        delay_timer: timer::create_threaded_counter(std::time::Duration::from_micros(16667)),
-        keyboard: keyboard::create(),
+pub fn builder() -> Builder {
+    return Builder {
+        headless: false,
+        memory_faults: false,
+        instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+        timing: timing::Mode::Fixed,
+        layout: ram::default_layout(),
+        font: font::default(),
+        tone: audio::default_tone(),
+        palette: palette::default(),
+        scale: 4,
+        screenshot_format: export::Format::Png,
+        theme: theme::default(),
+        keymap: keymap::default(),
+        persistence: 0,
+        orientation: orientation::Orientation::default(),
+        screen: graphics::Mode::Text,
+        seed: None,
+        rng: rng::Mode::Xorshift,
+        profile: quirks::Profile::Modern,
+        display_wait: None,
     };
 }
@@ -1,10 +1,16 @@
+use super::orientation::Orientation;
+use super::phosphor::Phosphor;
+use super::theme::Theme;
 use log;
 
 pub const HEIGHT: usize = 32;
 pub const WIDTH: usize = 64;
 
+// One bit per pixel, column 0 being the most significant bit
+pub type Row = u64;
+
 pub struct Display {
-    pub buffer: [[bool; WIDTH]; HEIGHT],
+    pub buffer: [Row; HEIGHT],
 }
 
 fn clear() {
@@ -14,37 +20,57 @@ fn clear() {
     print!("\x1B[2J");
 }
 
-const OFF: &str = "⬛";
-const ON: &str = "⬜";
+pub trait Screen {
+    // With persistence on, phosphor has the glow of recently cleared pixels
+    fn present(&mut self, display: &Display, phosphor: Option<&Phosphor>);
+}
 
 pub fn create_display() -> Display {
     return Display {
-        buffer: [[false; WIDTH]; HEIGHT],
+        buffer: [0; HEIGHT],
     };
 }
 
 impl Display {
-    // Returns true if pixel was set to 0
-    pub fn xor(&mut self, val: bool, h: usize, w: usize) -> bool {
-        if val {
-            let prev_val = self.buffer[h][w];
-            self.buffer[h][w] = !prev_val;
-            return prev_val;
-        }
-        return false;
+    // Returns true if any pixel was set to 0
+    pub fn xor_row(&mut self, h: usize, bits: Row) -> bool {
+        let collision = self.buffer[h] & bits != 0;
+        self.buffer[h] ^= bits;
+        return collision;
+    }
+    pub fn get(&self, h: usize, w: usize) -> bool {
+        return (self.buffer[h] >> (WIDTH - 1 - w)) & 1 != 0;
     }
     pub fn clear(&mut self) {
-        for ele in self.buffer.iter_mut() {
-            (*ele).fill(false);
-        }
+        self.buffer.fill(0);
     }
-    pub fn draw(&self) {
+}
+
+pub struct Terminal {
+    pub theme: Theme,
+    pub turn: Orientation,
+}
+
+impl Screen for Terminal {
+    fn present(&mut self, display: &Display, phosphor: Option<&Phosphor>) {
         clear();
-        for i in 0..HEIGHT {
-            for j in 0..WIDTH {
-                print!("{}", if self.buffer[i][j] { ON } else { OFF });
+        let (height, width) = self.turn.size();
+        for i in 0..height {
+            for j in 0..width {
+                let (h, w) = self.turn.source(i, j);
+                let lit = display.get(h, w);
+                match phosphor {
+                    Some(phosphor) if !lit => print!("{}", self.theme.faded(phosphor.level(h, w))),
+                    _ => print!("{}", self.theme.pixel(lit as usize)),
+                }
             }
-            println!();
+            println!("{}", self.theme.reset());
         }
     }
 }
+
+pub struct NullScreen {}
+
+impl Screen for NullScreen {
+    fn present(&mut self, _display: &Display, _phosphor: Option<&Phosphor>) {}
+}
@@ -0,0 +1,282 @@
+use log::{error, info, warn};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::str::FromStr;
+
+pub const SAMPLE_RATE: u32 = 44100;
+const FRAME_RATE: u32 = 60;
+pub const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / FRAME_RATE;
+
+// The buzzer played while the sound timer is non-zero
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    pub frequency: f64,
+    pub volume: f64, // 0 to 1
+}
+
+pub fn default_tone() -> Tone {
+    return Tone {
+        frequency: 440.0,
+        volume: 0.25,
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sound {
+    Tone(Tone),
+    // XO-CHIP: 128 one bit samples, most significant first, looped at rate samples per second
+    Pattern {
+        bits: [u8; 16],
+        rate: f64,
+        volume: f64,
+    },
+}
+
+// XO-CHIP's FX3A: 64 is 4000 samples per second, and every 48 steps double it
+pub fn pattern_rate(pitch: u8) -> f64 {
+    return 4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0);
+}
+
+impl Sound {
+    // Times per second the sound repeats
+    fn loop_rate(&self) -> f64 {
+        match self {
+            Sound::Tone(tone) => return tone.frequency,
+            Sound::Pattern { rate, .. } => return rate / 128.0,
+        }
+    }
+
+    // Whether the wave is high at the given fraction of its loop
+    fn high(&self, phase: f64) -> bool {
+        match self {
+            Sound::Tone(_) => return phase < 0.5,
+            Sound::Pattern { bits, .. } => {
+                let bit = (phase * 128.0) as usize % 128;
+                return bits[bit / 8] >> (7 - bit % 8) & 1 != 0;
+            }
+        }
+    }
+
+    fn volume(&self) -> f64 {
+        match self {
+            Sound::Tone(tone) => return tone.volume,
+            Sound::Pattern { volume, .. } => return *volume,
+        }
+    }
+
+    // The pitch heard, for outputs that can only play a square wave: one cycle per rising edge
+    fn frequency(&self) -> f64 {
+        match self {
+            Sound::Tone(tone) => return tone.frequency,
+            Sound::Pattern { .. } => {
+                let rises = (0..128)
+                    .filter(|bit| {
+                        !self.high(*bit as f64 / 128.0) && self.high((bit + 1) as f64 / 128.0)
+                    })
+                    .count();
+                return self.loop_rate() * rises as f64;
+            }
+        }
+    }
+}
+
+pub trait AudioSink {
+    // Called once at the end of every frame with what to play for its duration
+    fn frame(&mut self, sound: Option<Sound>);
+}
+
+pub struct NullAudio {}
+
+impl AudioSink for NullAudio {
+    fn frame(&mut self, _sound: Option<Sound>) {}
+}
+
+// The PC speaker through the console. It has no volume, and needs write access to /dev/console
+pub struct Speaker {
+    hertz: u16, // 0 when silent
+    failed: bool,
+}
+
+impl AudioSink for Speaker {
+    fn frame(&mut self, sound: Option<Sound>) {
+        let hertz = sound.map(|sound| sound.frequency() as u16).unwrap_or(0);
+        if self.failed || hertz == self.hertz {
+            return;
+        }
+        self.hertz = hertz;
+        if let Err(e) = beep::beep(hertz) {
+            error!("Speaker failed, sound is off: {}", e);
+            self.failed = true;
+        }
+    }
+}
+
+impl Drop for Speaker {
+    fn drop(&mut self) {
+        if self.hertz != 0 && !self.failed {
+            let _ = beep::beep(0);
+        }
+    }
+}
+
+// Rings the terminal bell when a tone starts, or flashes the screen for as long as it plays
+pub struct Bell {
+    flash: bool,
+    playing: bool,
+}
+
+impl AudioSink for Bell {
+    fn frame(&mut self, sound: Option<Sound>) {
+        if sound.is_some() == self.playing {
+            return;
+        }
+        self.playing = sound.is_some();
+        match (self.flash, self.playing) {
+            (false, true) => print!("\x07"),
+            (false, false) => {}
+            // Reverse video on and off
+            (true, true) => print!("\x1B[?5h"),
+            (true, false) => print!("\x1B[?5l"),
+        }
+        let _ = std::io::stdout().flush();
+    }
+}
+
+// 16 bit mono PCM. The header is brought up to date every second and when the writer is
+// dropped, so a killed session still leaves a playable file
+pub struct WavWriter {
+    out: BufWriter<File>,
+    samples: u32,
+    phase: f64, // Fraction of a loop, carried across frames and pitch changes so the wave has no seams
+}
+
+pub fn create_wav(path: &str) -> Result<WavWriter, String> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut writer = WavWriter {
+        out: BufWriter::new(file),
+        samples: 0,
+        phase: 0.0,
+    };
+    writer.header().map_err(|e| format!("{}: {}", path, e))?;
+    return Ok(writer);
+}
+
+impl WavWriter {
+    fn header(&mut self) -> std::io::Result<()> {
+        let data = 2 * self.samples;
+        self.out.write_all(b"RIFF")?;
+        self.out.write_all(&(36 + data).to_le_bytes())?;
+        self.out.write_all(b"WAVEfmt ")?;
+        self.out.write_all(&16u32.to_le_bytes())?;
+        self.out.write_all(&1u16.to_le_bytes())?; // PCM
+        self.out.write_all(&1u16.to_le_bytes())?; // Mono
+        self.out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        self.out.write_all(&(2 * SAMPLE_RATE).to_le_bytes())?;
+        self.out.write_all(&2u16.to_le_bytes())?;
+        self.out.write_all(&16u16.to_le_bytes())?;
+        self.out.write_all(b"data")?;
+        self.out.write_all(&data.to_le_bytes())?;
+        return Ok(());
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.out.seek(SeekFrom::Start(0))?;
+        self.header()?;
+        return self.out.flush();
+    }
+}
+
+impl AudioSink for WavWriter {
+    fn frame(&mut self, sound: Option<Sound>) {
+        let mut bytes = Vec::with_capacity(2 * SAMPLES_PER_FRAME as usize);
+        for _ in 0..SAMPLES_PER_FRAME {
+            let sample = match sound {
+                Some(sound) => {
+                    let level = (sound.volume().clamp(0.0, 1.0) * i16::MAX as f64) as i16;
+                    let sample = if sound.high(self.phase) {
+                        level
+                    } else {
+                        -level
+                    };
+                    self.phase = (self.phase + sound.loop_rate() / SAMPLE_RATE as f64).fract();
+                    sample
+                }
+                None => 0,
+            };
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        if let Err(e) = self.out.write_all(&bytes) {
+            error!("Wav write failed: {}", e);
+        }
+        self.samples += SAMPLES_PER_FRAME;
+        if self.samples.is_multiple_of(SAMPLE_RATE) {
+            let result = self.finish().and_then(|_| self.out.seek(SeekFrom::End(0)));
+            if let Err(e) = result {
+                error!("Wav write failed: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            error!("Wav write failed: {}", e);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    None,
+    Speaker,
+    Bell,
+    Flash,
+    Wav(String),
+}
+
+pub fn create(output: &Output) -> Result<Box<dyn AudioSink>, String> {
+    match output {
+        Output::None => return Ok(Box::new(NullAudio {})),
+        Output::Speaker => {
+            // beep panics if it can't open the console, so check first
+            if let Err(e) = OpenOptions::new().append(true).open("/dev/console") {
+                warn!("No speaker ({}), using the terminal bell", e);
+                return create(&Output::Bell);
+            }
+            info!("Sound on the PC speaker");
+            return Ok(Box::new(Speaker {
+                hertz: 0,
+                failed: false,
+            }));
+        }
+        Output::Bell => {
+            return Ok(Box::new(Bell {
+                flash: false,
+                playing: false,
+            }))
+        }
+        Output::Flash => {
+            return Ok(Box::new(Bell {
+                flash: true,
+                playing: false,
+            }))
+        }
+        Output::Wav(path) => return Ok(Box::new(create_wav(path)?)),
+    }
+}
+
+// "none", "speaker", "bell", "flash" or the path of a .wav file
+impl FromStr for Output {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Output, String> {
+        match text {
+            "none" => return Ok(Output::None),
+            "speaker" => return Ok(Output::Speaker),
+            "bell" => return Ok(Output::Bell),
+            "flash" => return Ok(Output::Flash),
+            path if path.ends_with(".wav") => return Ok(Output::Wav(path.to_string())),
+            _ => return Err(format!("Unknown audio output {}", text)),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use super::instruction::Instruction;
+
+// One opcode in the usual CHIP-8 assembly mnemonics, with XO-CHIP's F002 and FX3A. Anything
+// the interpreter doesn't run comes out as data
+pub fn disassemble(inst: u16) -> String {
+    let x = inst.x_register_of();
+    let y = inst.y_register_of();
+    let n = inst.fourth_nibble_of();
+    let nn = inst.second_byte_of();
+    let nnn = inst.jump_addr();
+    match inst.instruction_of() {
+        0x0 if nnn == 0x0E0 => return "CLS".to_string(),
+        0x0 if nnn == 0x0EE => return "RET".to_string(),
+        0x0 => return format!("SYS {:#05x}", nnn),
+        0x1 => return format!("JP {:#05x}", nnn),
+        0x2 => return format!("CALL {:#05x}", nnn),
+        0x3 => return format!("SE V{:X}, {:#04x}", x, nn),
+        0x4 => return format!("SNE V{:X}, {:#04x}", x, nn),
+        0x5 => return format!("SE V{:X}, V{:X}", x, y),
+        0x6 => return format!("LD V{:X}, {:#04x}", x, nn),
+        0x7 => return format!("ADD V{:X}, {:#04x}", x, nn),
+        0x8 => {
+            let op = match n {
+                0x0 => "LD",
+                0x1 => "OR",
+                0x2 => "AND",
+                0x3 => "XOR",
+                0x4 => "ADD",
+                0x5 => "SUB",
+                0x6 => "SHR",
+                0x7 => "SUBN",
+                0xE => "SHL",
+                _ => return data(inst),
+            };
+            return format!("{} V{:X}, V{:X}", op, x, y);
+        }
+        0x9 => return format!("SNE V{:X}, V{:X}", x, y),
+        0xA => return format!("LD I, {:#05x}", nnn),
+        0xB => return format!("JP V0, {:#05x}", nnn),
+        0xC => return format!("RND V{:X}, {:#04x}", x, nn),
+        0xD => return format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE if nn == 0x9E => return format!("SKP V{:X}", x),
+        0xE if nn == 0xA1 => return format!("SKNP V{:X}", x),
+        0xF => match nn {
+            0x02 => return "AUDIO".to_string(),
+            0x07 => return format!("LD V{:X}, DT", x),
+            0x0A => return format!("LD V{:X}, K", x),
+            0x15 => return format!("LD DT, V{:X}", x),
+            0x18 => return format!("LD ST, V{:X}", x),
+            0x1E => return format!("ADD I, V{:X}", x),
+            0x29 => return format!("LD F, V{:X}", x),
+            0x30 => return format!("LD HF, V{:X}", x),
+            0x33 => return format!("LD B, V{:X}", x),
+            0x3A => return format!("PITCH V{:X}", x),
+            0x55 => return format!("LD [I], V{:X}", x),
+            0x65 => return format!("LD V{:X}, [I]", x),
+            _ => return data(inst),
+        },
+        _ => return data(inst),
+    }
+}
+
+fn data(inst: u16) -> String {
+    return format!("DW {:#06x}", inst);
+}
@@ -0,0 +1,109 @@
+use super::ascii::Display;
+use super::orientation::Orientation;
+use super::palette::Palette;
+use std::path::Path;
+use std::str::FromStr;
+
+// What the screenshot hotkey writes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Png,
+    Pbm,
+    Text,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Png => return "png",
+            Format::Pbm => return "pbm",
+            Format::Text => return "txt",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Format, String> {
+        match text {
+            "png" => return Ok(Format::Png),
+            "pbm" => return Ok(Format::Pbm),
+            "txt" => return Ok(Format::Text),
+            _ => return Err(format!("Unknown screenshot format {}", text)),
+        }
+    }
+}
+
+fn text(display: &Display, turn: &Orientation) -> Vec<u8> {
+    let (height, width) = turn.size();
+    let mut out = String::new();
+    for i in 0..height {
+        for j in 0..width {
+            out.push(if turn.get(display, i, j) { '#' } else { '.' });
+        }
+        out.push('\n');
+    }
+    return out.into_bytes();
+}
+
+// Plain pbm, where 1 is ink: lit pixels come out black on white whatever the palette
+fn pbm(display: &Display, turn: &Orientation, scale: usize) -> Vec<u8> {
+    let (height, width) = turn.size();
+    let mut out = format!("P1\n{} {}\n", width * scale, height * scale);
+    for i in 0..height * scale {
+        let row: Vec<&str> = (0..width * scale)
+            .map(|j| {
+                if turn.get(display, i / scale, j / scale) {
+                    "1"
+                } else {
+                    "0"
+                }
+            })
+            .collect();
+        out.push_str(&row.join(" "));
+        out.push('\n');
+    }
+    return out.into_bytes();
+}
+
+fn png(
+    display: &Display,
+    turn: &Orientation,
+    scale: usize,
+    palette: &Palette,
+) -> Result<Vec<u8>, String> {
+    let (height, width) = turn.size();
+    let mut pixels = Vec::with_capacity(3 * width * height * scale * scale);
+    for i in 0..height * scale {
+        for j in 0..width * scale {
+            pixels.extend_from_slice(&palette.color(turn.get(display, i / scale, j / scale)));
+        }
+    }
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    return Ok(out);
+}
+
+// Images are scaled up by whole pixels; the text grid is always one character per pixel
+pub fn save(
+    display: &Display,
+    path: &str,
+    turn: &Orientation,
+    scale: usize,
+    palette: &Palette,
+) -> Result<(), String> {
+    let scale = scale.max(1);
+    let data = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("png") => png(display, turn, scale, palette)?,
+        Some("pbm") => pbm(display, turn, scale),
+        _ => text(display, turn),
+    };
+    return std::fs::write(path, data).map_err(|e| format!("{}: {}", path, e));
+}
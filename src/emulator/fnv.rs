@@ -0,0 +1,24 @@
+use std::hash::Hasher;
+
+// 64-bit FNV-1a. Unlike the std hasher its output is stable, so it can be written to files
+pub struct Fnv {
+    state: u64,
+}
+
+pub fn create() -> Fnv {
+    return Fnv {
+        state: 0xcbf29ce484222325,
+    };
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        return self.state;
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(0x100000001b3);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use super::ascii::{Display, Screen};
+use super::orientation::Orientation;
+use super::palette::Palette;
+use super::phosphor::Phosphor;
+use base64::Engine;
+use log::{info, warn};
+use std::io::{IsTerminal, Write};
+use std::str::FromStr;
+
+// Terminal image protocols that show the screen as real pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Sixel,
+    Kitty,
+}
+
+// What the terminal renderer draws with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Text,
+    // Whichever protocol the terminal supports, or else text
+    Auto,
+    // The protocol, even if the terminal doesn't seem to support it
+    Only(Protocol),
+}
+
+// Terminals don't answer queries without putting stdin in raw mode, so support is guessed
+// from the environment they set
+pub fn detect() -> Option<Protocol> {
+    return detect_in(std::io::stdout().is_terminal(), |name| std::env::var(name).ok());
+}
+
+// detect for the given output and environment variables
+pub fn detect_in(is_terminal: bool, env: impl Fn(&str) -> Option<String>) -> Option<Protocol> {
+    if !is_terminal {
+        return None;
+    }
+    let var = |name: &str| env(name).unwrap_or_default();
+    let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+    if env("KITTY_WINDOW_ID").is_some()
+        || term.contains("kitty")
+        || term.contains("ghostty")
+        || ["WezTerm", "ghostty"].contains(&program.as_str())
+    {
+        return Some(Protocol::Kitty);
+    }
+    if term.contains("sixel")
+        || ["foot", "foot-extra", "mlterm", "yaft-256color", "contour"].contains(&term.as_str())
+        || program == "mintty"
+    {
+        return Some(Protocol::Sixel);
+    }
+    return None;
+}
+
+// The protocol to use for the given mode, None for text
+pub fn choose(mode: Mode) -> Option<Protocol> {
+    return choose_from(mode, detect());
+}
+
+// choose given what was detected
+pub fn choose_from(mode: Mode, detected: Option<Protocol>) -> Option<Protocol> {
+    match mode {
+        Mode::Text => return None,
+        Mode::Auto => return detected,
+        Mode::Only(protocol) => {
+            if detected != Some(protocol) {
+                warn!(
+                    "The terminal doesn't seem to support {:?} graphics, using them anyway",
+                    protocol
+                );
+            }
+            return Some(protocol);
+        }
+    }
+}
+
+// One palette index per scaled pixel, row by row
+fn pixels(display: &Display, turn: &Orientation, scale: usize) -> (usize, usize, Vec<u8>) {
+    let (height, width) = turn.size();
+    let mut out = Vec::with_capacity(width * height * scale * scale);
+    for i in 0..height * scale {
+        for j in 0..width * scale {
+            out.push(turn.get(display, i / scale, j / scale) as u8);
+        }
+    }
+    return (width * scale, height * scale, out);
+}
+
+// DEC sixel: bands of 6 rows, each colour painted over the band in turn, with runs compressed
+pub fn sixel(width: usize, height: usize, pixels: &[u8], palette: &Palette) -> String {
+    let mut out = format!("\x1BPq\"1;1;{};{}", width, height);
+    for (n, color) in [palette.background, palette.foreground].iter().enumerate() {
+        let [r, g, b] = color.map(|channel| (channel as u32 * 100 + 127) / 255);
+        out.push_str(&format!("#{};2;{};{};{}", n, r, g, b));
+    }
+    for top in (0..height).step_by(6) {
+        for color in 0..2u8 {
+            out.push_str(&format!("#{}", color));
+            let mut run: Option<(u8, usize)> = None;
+            for j in 0..width {
+                let mut bits = 0;
+                for k in 0..6.min(height - top) {
+                    if pixels[(top + k) * width + j] == color {
+                        bits |= 1 << k;
+                    }
+                }
+                let code = b'?' + bits;
+                run = match run {
+                    Some((last, count)) if last == code => Some((last, count + 1)),
+                    Some(last) => {
+                        push_run(&mut out, last);
+                        Some((code, 1))
+                    }
+                    None => Some((code, 1)),
+                };
+            }
+            if let Some(last) = run {
+                push_run(&mut out, last);
+            }
+            out.push(if color == 0 { '$' } else { '-' });
+        }
+    }
+    out.push_str("\x1B\\");
+    return out;
+}
+
+fn push_run(out: &mut String, (code, count): (u8, usize)) {
+    if count > 3 {
+        out.push_str(&format!("!{}{}", count, code as char));
+    } else {
+        out.push_str(&(code as char).to_string().repeat(count));
+    }
+}
+
+// Kitty's graphics protocol: rgb24 in base64, sent in chunks of at most 4096 bytes. Image 1 is
+// replaced every frame, without moving the cursor or waiting for the terminal's answer
+pub fn kitty(width: usize, height: usize, pixels: &[u8], palette: &Palette) -> String {
+    let rgb: Vec<u8> = pixels
+        .iter()
+        .flat_map(|pixel| palette.color(*pixel != 0))
+        .collect();
+    let data = base64::engine::general_purpose::STANDARD.encode(rgb);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    let mut out = String::new();
+    for (n, chunk) in chunks.iter().enumerate() {
+        let more = (n + 1 < chunks.len()) as u8;
+        if n == 0 {
+            out.push_str(&format!(
+                "\x1B_Ga=T,f=24,s={},v={},i=1,C=1,q=2,m={};",
+                width, height, more
+            ));
+        } else {
+            out.push_str(&format!("\x1B_Gm={};", more));
+        }
+        out.push_str(std::str::from_utf8(chunk).unwrap());
+        out.push_str("\x1B\\");
+    }
+    return out;
+}
+
+pub fn encode(
+    protocol: Protocol,
+    display: &Display,
+    turn: &Orientation,
+    scale: usize,
+    palette: &Palette,
+) -> String {
+    let (width, height, pixels) = pixels(display, turn, scale.max(1));
+    match protocol {
+        Protocol::Sixel => return sixel(width, height, &pixels, palette),
+        Protocol::Kitty => return kitty(width, height, &pixels, palette),
+    }
+}
+
+// Draws the screen as an image at the top left of the terminal
+pub struct Graphics {
+    pub protocol: Protocol,
+    pub turn: Orientation,
+    pub scale: usize,
+    pub palette: Palette,
+}
+
+pub fn create_graphics(
+    protocol: Protocol,
+    turn: Orientation,
+    scale: usize,
+    palette: Palette,
+) -> Graphics {
+    info!("Drawing with {:?} graphics", protocol);
+    print!("\x1B[2J");
+    return Graphics {
+        protocol,
+        turn,
+        scale,
+        palette,
+    };
+}
+
+impl Screen for Graphics {
+    fn present(&mut self, display: &Display, _phosphor: Option<&Phosphor>) {
+        let image = encode(
+            self.protocol,
+            display,
+            &self.turn,
+            self.scale,
+            &self.palette,
+        );
+        print!("\x1B[H{}", image);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+// "text", "auto", "sixel" or "kitty"
+impl FromStr for Mode {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Mode, String> {
+        match text {
+            "text" => return Ok(Mode::Text),
+            "auto" => return Ok(Mode::Auto),
+            "sixel" => return Ok(Mode::Only(Protocol::Sixel)),
+            "kitty" => return Ok(Mode::Only(Protocol::Kitty)),
+            _ => return Err(format!("Unknown screen {}", text)),
+        }
+    }
+}
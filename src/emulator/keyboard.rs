@@ -1,57 +1,176 @@
 use log::trace;
 use device_query::{DeviceQuery, DeviceState, Keycode};
+use super::keymap::Keymap;
+
+// Emulator hotkeys, read alongside the keypad but never seen by the program
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Controls {
+    // Pressed since the last poll
+    pub pause: bool,
+    pub advance: bool,
+    pub record: bool,
+    pub screenshot: bool,
+    pub quit: bool,
+    // Held
+    pub slow_motion: bool,
+    pub fast_forward: bool,
+}
+
+pub trait Input {
+    // Bit n is set while key n is held. Polled once at the start of every frame
+    fn poll(&mut self, frame: u64) -> u16;
+    // Polled once per frame while running in real time
+    fn controls(&mut self) -> Controls {
+        return Controls::default();
+    }
+}
 
 pub struct Keyboard {
     keymap: [i32; 100],
     state: DeviceState,
+    held: Vec<Keycode>,
 }
 
-pub fn create() -> Keyboard {
+pub fn create(keymap: &Keymap) -> Keyboard {
     let mut kb = Keyboard {
         keymap: [-1; 100],
         state: DeviceState::new(),
+        held: Vec::new(),
     };
-    kb.keymap[Keycode::Key1 as usize] = 1;
-    kb.keymap[Keycode::Key2 as usize] = 2;
-    kb.keymap[Keycode::Key3 as usize] = 3;
-    kb.keymap[Keycode::Key4 as usize] = 0xC;
-    kb.keymap[Keycode::Q as usize] = 0x4;
-    kb.keymap[Keycode::W as usize] = 0x5;
-    kb.keymap[Keycode::E as usize] = 0x6;
-    kb.keymap[Keycode::R as usize] = 0xD;
-    kb.keymap[Keycode::A as usize] = 0x7;
-    kb.keymap[Keycode::S as usize] = 0x8;
-    kb.keymap[Keycode::D as usize] = 0x9;
-    kb.keymap[Keycode::F as usize] = 0xE;
-    kb.keymap[Keycode::Z as usize] = 0xA;
-    kb.keymap[Keycode::X as usize] = 0x0;
-    kb.keymap[Keycode::C as usize] = 0xB;
-    kb.keymap[Keycode::V as usize] = 0xF;
+    for (n, key) in keymap.keys.iter().enumerate() {
+        if let Some(code) = keycode(*key) {
+            kb.keymap[code as usize] = n as i32;
+        }
+    }
     return kb;
 }
 
+fn keycode(key: char) -> Option<Keycode> {
+    match key {
+        '0' => return Some(Keycode::Key0),
+        '1' => return Some(Keycode::Key1),
+        '2' => return Some(Keycode::Key2),
+        '3' => return Some(Keycode::Key3),
+        '4' => return Some(Keycode::Key4),
+        '5' => return Some(Keycode::Key5),
+        '6' => return Some(Keycode::Key6),
+        '7' => return Some(Keycode::Key7),
+        '8' => return Some(Keycode::Key8),
+        '9' => return Some(Keycode::Key9),
+        'a' => return Some(Keycode::A),
+        'b' => return Some(Keycode::B),
+        'c' => return Some(Keycode::C),
+        'd' => return Some(Keycode::D),
+        'e' => return Some(Keycode::E),
+        'f' => return Some(Keycode::F),
+        'g' => return Some(Keycode::G),
+        'h' => return Some(Keycode::H),
+        'i' => return Some(Keycode::I),
+        'j' => return Some(Keycode::J),
+        'k' => return Some(Keycode::K),
+        'l' => return Some(Keycode::L),
+        'm' => return Some(Keycode::M),
+        'n' => return Some(Keycode::N),
+        'o' => return Some(Keycode::O),
+        'p' => return Some(Keycode::P),
+        'q' => return Some(Keycode::Q),
+        'r' => return Some(Keycode::R),
+        's' => return Some(Keycode::S),
+        't' => return Some(Keycode::T),
+        'u' => return Some(Keycode::U),
+        'v' => return Some(Keycode::V),
+        'w' => return Some(Keycode::W),
+        'x' => return Some(Keycode::X),
+        'y' => return Some(Keycode::Y),
+        'z' => return Some(Keycode::Z),
+        _ => return None,
+    }
+}
 
-impl Keyboard {
-    // Returns -1 if nothing is pressed
-    pub fn get_press(&mut self) -> i32 {
-        let keys = self.state.get_keys();
-        trace!("Keys:");
-        for key in keys.iter() {
-            trace!("{}", key.clone() as usize);
-            if self.keymap[key.clone() as usize] != -1 {
-                return self.keymap[key.clone() as usize];
+impl Input for Keyboard {
+    fn poll(&mut self, _frame: u64) -> u16 {
+        let mut keys = 0;
+        for key in self.state.get_keys().iter() {
+            let val = self.keymap[key.clone() as usize];
+            if val != -1 {
+                keys |= 1 << val;
             }
         }
-        return -1;
+        trace!("Keys {:#06x}", keys);
+        return keys;
     }
-    pub fn is_pressed(&mut self, but: i32) -> bool {
-        trace!("IsPressed {}", but);
-        for key in self.state.get_keys().iter() {
-            if self.keymap[key.clone() as usize] == but {
 
-                return true;
-            }
+    // F1 pauses, F2 advances a frame while paused, F3 and F4 are held for slow motion and fast
+    // forward, F5 starts and stops recording video, F6 takes a screenshot and Escape quits
+    fn controls(&mut self) -> Controls {
+        let keys = self.state.get_keys();
+        let pressed = |key: Keycode| keys.contains(&key) && !self.held.contains(&key);
+        let controls = Controls {
+            pause: pressed(Keycode::F1),
+            advance: pressed(Keycode::F2),
+            record: pressed(Keycode::F5),
+            screenshot: pressed(Keycode::F6),
+            quit: pressed(Keycode::Escape),
+            slow_motion: keys.contains(&Keycode::F3),
+            fast_forward: keys.contains(&Keycode::F4),
+        };
+        self.held = keys;
+        return controls;
+    }
+}
+
+pub struct NullInput {}
+
+impl Input for NullInput {
+    fn poll(&mut self, _frame: u64) -> u16 {
+        return 0;
+    }
+}
+
+// Replays key states, each held from its frame on until the next one
+pub struct ScriptedInput {
+    events: Vec<(u64, u16)>,
+    next: usize,
+    keys: u16,
+}
+
+pub fn create_scripted(events: Vec<(u64, u16)>) -> ScriptedInput {
+    return ScriptedInput {
+        events,
+        next: 0,
+        keys: 0,
+    };
+}
+
+impl Input for ScriptedInput {
+    fn poll(&mut self, frame: u64) -> u16 {
+        while self.next < self.events.len() && self.events[self.next].0 <= frame {
+            self.keys = self.events[self.next].1;
+            self.next += 1;
+        }
+        return self.keys;
+    }
+}
+
+// One entry per line: a frame number followed by the hex digits of the keys held from then on,
+// e.g. "120 4 6". A frame with no keys releases everything. Lines starting with # are ignored
+pub fn load_script(path: &str) -> Result<Vec<(u64, u16)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut events = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let bad = || format!("{}:{}: bad key script line", path, n + 1);
+        let mut fields = line.split_whitespace();
+        let frame: u64 = fields.next().unwrap().parse().map_err(|_| bad())?;
+        let mut keys = 0;
+        for key in fields {
+            keys |= 1 << u8::from_str_radix(key, 16).ok().filter(|k| *k < 16).ok_or_else(bad)?;
         }
-        return false;
+        events.push((frame, keys));
     }
+    events.sort_by_key(|event| event.0);
+    return Ok(events);
 }
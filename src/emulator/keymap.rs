@@ -0,0 +1,49 @@
+use std::fmt;
+use std::str::FromStr;
+
+// The keyboard key for each keypad key from 0 to F, as letters and digits
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keymap {
+    pub keys: [char; 16],
+}
+
+// 1234 / QWER / ASDF / ZXCV, laid out like the VIP's keypad
+pub fn default() -> Keymap {
+    return "x123qweasdzc4rfv".parse().unwrap();
+}
+
+impl Keymap {
+    // The keypad key of a keyboard key
+    pub fn key(&self, typed: char) -> Option<usize> {
+        let typed = typed.to_ascii_lowercase();
+        return self.keys.iter().position(|key| *key == typed);
+    }
+}
+
+// 16 different letters or digits for keypad keys 0 to F, e.g. "x123qweasdzc4rfv"
+impl FromStr for Keymap {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Keymap, String> {
+        let keys: Vec<char> = text.to_lowercase().chars().collect();
+        if keys.len() != 16 {
+            return Err(format!("A keymap has 16 keys, not {}", keys.len()));
+        }
+        for (n, key) in keys.iter().enumerate() {
+            if !key.is_ascii_alphanumeric() {
+                return Err(format!("Keymap key {} isn't a letter or digit", key));
+            }
+            if keys[..n].contains(key) {
+                return Err(format!("Keymap key {} is used twice", key));
+            }
+        }
+        let mut keymap = Keymap { keys: ['0'; 16] };
+        keymap.keys.copy_from_slice(&keys);
+        return Ok(keymap);
+    }
+}
+
+impl fmt::Display for Keymap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.keys.iter().collect::<String>());
+    }
+}
@@ -0,0 +1,227 @@
+use super::fnv;
+use super::quirks::Profile;
+use super::ram::Layout;
+use super::rng::Rng;
+use super::timing;
+use log::{error, info};
+use std::fs::File;
+use std::hash::Hasher;
+use std::io::{LineWriter, Write};
+
+// Text format, one entry per line:
+//   rusty-movie 1
+//   rom <hash of the loaded program memory>
+//   memory <ram size> <load address> <font address>
+//   font <hash of the glyphs in ram>
+//   memory-faults <0 or 1>
+//   ipf <instructions per frame>
+//   timing <mode>
+//   quirks <profile>
+//   rng <rng state at frame 0>
+//   display-wait <0 or 1>          only when set apart from the profile
+//   keys <frame> <keypad bits>     whenever the keypad changes
+//   check <frame> <state hash>     every CHECKPOINT_INTERVAL frames
+//   end <frame>
+const MAGIC: &str = "rusty-movie 1";
+pub const CHECKPOINT_INTERVAL: u64 = 60;
+
+pub fn rom_hash(mem: &[u8]) -> u64 {
+    let mut hasher = fnv::create();
+    hasher.write(mem);
+    return hasher.finish();
+}
+
+pub struct Movie {
+    pub rom: u64,
+    // Missing from movies recorded before the layout was configurable
+    pub layout: Option<Layout>,
+    // Both missing from movies recorded before fonts were configurable
+    pub font: Option<u64>,
+    pub memory_faults: Option<bool>,
+    pub instructions_per_frame: Option<u64>,
+    pub timing: Option<timing::Mode>,
+    pub profile: Profile,
+    // Left to the profile when missing
+    pub display_wait: Option<bool>,
+    pub rng: Rng,
+    pub keys: Vec<(u64, u16)>,
+    pub checks: Vec<(u64, u64)>,
+    pub end: Option<u64>,
+}
+
+fn parse_hex(text: Option<&str>, line: usize) -> Result<u64, String> {
+    return text
+        .and_then(|text| u64::from_str_radix(text, 16).ok())
+        .ok_or(format!("Bad movie line {}", line));
+}
+
+fn parse_frame(text: Option<&str>, line: usize) -> Result<u64, String> {
+    return text
+        .and_then(|text| text.parse().ok())
+        .ok_or(format!("Bad movie line {}", line));
+}
+
+pub fn load(path: &str) -> Result<Movie, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut lines = text.lines();
+    if lines.next() != Some(MAGIC) {
+        return Err(format!("{} is not a movie file", path));
+    }
+    let mut movie = Movie {
+        rom: 0,
+        layout: None,
+        font: None,
+        memory_faults: None,
+        instructions_per_frame: None,
+        timing: None,
+        profile: Profile::Modern,
+        display_wait: None,
+        rng: Rng::Fixed {
+            bytes: Vec::new(),
+            position: 0,
+        },
+        keys: Vec::new(),
+        checks: Vec::new(),
+        end: None,
+    };
+    for (n, line) in lines.enumerate() {
+        let n = n + 2;
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("rom") => movie.rom = parse_hex(fields.next(), n)?,
+            Some("memory") => {
+                movie.layout = Some(Layout {
+                    size: parse_hex(fields.next(), n)? as usize,
+                    load_address: parse_hex(fields.next(), n)? as usize,
+                    font_address: parse_hex(fields.next(), n)? as usize,
+                })
+            }
+            Some("font") => movie.font = Some(parse_hex(fields.next(), n)?),
+            Some("memory-faults") => {
+                movie.memory_faults = Some(parse_frame(fields.next(), n)? != 0)
+            }
+            Some("ipf") => movie.instructions_per_frame = Some(parse_frame(fields.next(), n)?),
+            Some("timing") => movie.timing = Some(fields.next().unwrap_or("").parse()?),
+            Some("quirks") => movie.profile = fields.next().unwrap_or("").parse()?,
+            Some("display-wait") => movie.display_wait = Some(parse_frame(fields.next(), n)? != 0),
+            Some("rng") => movie.rng = fields.next().unwrap_or("").parse()?,
+            Some("keys") => {
+                let frame = parse_frame(fields.next(), n)?;
+                movie.keys.push((frame, parse_hex(fields.next(), n)? as u16));
+            }
+            Some("check") => {
+                let frame = parse_frame(fields.next(), n)?;
+                movie.checks.push((frame, parse_hex(fields.next(), n)?));
+            }
+            Some("end") => movie.end = Some(parse_frame(fields.next(), n)?),
+            None => {}
+            _ => return Err(format!("Bad movie line {}", n)),
+        }
+    }
+    return Ok(movie);
+}
+
+pub struct Recorder {
+    // Line buffered, so a session killed with ctrl-c still leaves a usable movie
+    out: LineWriter<File>,
+    keys: u16,
+}
+
+pub fn create_recorder(
+    path: &str,
+    rom: u64,
+    layout: Layout,
+    instructions_per_frame: u64,
+    timing: timing::Mode,
+    profile: Profile,
+    rng: &Rng,
+) -> Result<Recorder, String> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut recorder = Recorder {
+        out: LineWriter::new(file),
+        keys: 0,
+    };
+    recorder.line(format!(
+        "{}\nrom {:016x}\nmemory {}\nipf {}\ntiming {}\nquirks {}\nrng {}",
+        MAGIC, rom, layout, instructions_per_frame, timing, profile, rng
+    ));
+    return Ok(recorder);
+}
+
+impl Recorder {
+    fn line(&mut self, line: String) {
+        if let Err(e) = writeln!(self.out, "{}", line) {
+            error!("Movie write failed: {}", e);
+        }
+    }
+    pub fn font(&mut self, hash: u64) {
+        self.line(format!("font {:016x}", hash));
+    }
+    pub fn memory_faults(&mut self, faults: bool) {
+        self.line(format!("memory-faults {}", faults as u8));
+    }
+    // Only when it isn't what the profile says
+    pub fn display_wait(&mut self, wait: bool) {
+        self.line(format!("display-wait {}", wait as u8));
+    }
+    pub fn keys(&mut self, frame: u64, keys: u16) {
+        if keys != self.keys {
+            self.keys = keys;
+            self.line(format!("keys {} {:04x}", frame, keys));
+        }
+    }
+    pub fn check(&mut self, frame: u64, hash: u64) {
+        self.line(format!("check {} {:016x}", frame, hash));
+    }
+    pub fn end(&mut self, frame: u64) {
+        self.line(format!("end {}", frame));
+    }
+}
+
+// Compares the state hashes of a movie being played back
+pub struct Player {
+    checks: Vec<(u64, u64)>,
+    next: usize,
+    end: Option<u64>,
+    desynced: bool,
+}
+
+pub fn create_player(movie: &Movie) -> Player {
+    return Player {
+        checks: movie.checks.clone(),
+        next: 0,
+        end: movie.end,
+        desynced: false,
+    };
+}
+
+impl Player {
+    pub fn check(&mut self, frame: u64, hash: u64) {
+        while self.next < self.checks.len() && self.checks[self.next].0 < frame {
+            self.next += 1;
+        }
+        if self.next == self.checks.len() || self.checks[self.next].0 != frame {
+            return;
+        }
+        let expected = self.checks[self.next].1;
+        if hash != expected && !self.desynced {
+            // Everything after the first difference differs as well
+            self.desynced = true;
+            error!(
+                "Movie desync at frame {}: state {:016x}, recorded {:016x}",
+                frame, hash, expected
+            );
+        }
+    }
+    pub fn finished(&self, frame: u64) -> bool {
+        match self.end {
+            Some(end) => {
+                if frame >= end {
+                    info!("Movie finished at frame {}", frame);
+                }
+                return frame >= end;
+            }
+            None => return false,
+        }
+    }
+}
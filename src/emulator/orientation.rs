@@ -0,0 +1,52 @@
+use super::ascii::{Display, HEIGHT, WIDTH};
+
+// How the display is turned for presentation, for ROMs made for a screen on its side. The
+// program always draws and collides on the unrotated display
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    // Degrees clockwise: 0, 90, 180 or 270
+    pub rotation: u16,
+    // Mirrored left to right and top to bottom, after rotating
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Orientation {
+    // Rows and columns of the presented picture
+    pub fn size(&self) -> (usize, usize) {
+        if self.rotation % 180 == 90 {
+            return (WIDTH, HEIGHT);
+        }
+        return (HEIGHT, WIDTH);
+    }
+
+    // The display pixel shown at row i, column j of the presented picture
+    pub fn source(&self, i: usize, j: usize) -> (usize, usize) {
+        let (rows, columns) = self.size();
+        let i = if self.flip_vertical { rows - 1 - i } else { i };
+        let j = if self.flip_horizontal {
+            columns - 1 - j
+        } else {
+            j
+        };
+        match self.rotation {
+            90 => return (HEIGHT - 1 - j, i),
+            180 => return (HEIGHT - 1 - i, WIDTH - 1 - j),
+            270 => return (j, WIDTH - 1 - i),
+            _ => return (i, j),
+        }
+    }
+
+    pub fn get(&self, display: &Display, i: usize, j: usize) -> bool {
+        let (h, w) = self.source(i, j);
+        return display.get(h, w);
+    }
+}
+
+// Degrees of clockwise rotation
+pub fn parse_rotation(text: &str) -> Result<u16, String> {
+    match text {
+        "0" | "90" | "180" | "270" => return Ok(text.parse().unwrap()),
+        _ => return Err(format!("Rotation is 0, 90, 180 or 270, not {}", text)),
+    }
+}
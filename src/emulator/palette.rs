@@ -0,0 +1,62 @@
+use std::fmt;
+use std::str::FromStr;
+
+pub type Rgb = [u8; 3];
+
+// Colours of unlit and lit pixels in images and video
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub background: Rgb,
+    pub foreground: Rgb,
+}
+
+pub fn default() -> Palette {
+    return Palette {
+        background: [0x00, 0x00, 0x00],
+        foreground: [0xFF, 0xFF, 0xFF],
+    };
+}
+
+impl Palette {
+    pub fn color(&self, lit: bool) -> Rgb {
+        return if lit {
+            self.foreground
+        } else {
+            self.background
+        };
+    }
+}
+
+fn parse_rgb(text: &str) -> Result<Rgb, String> {
+    let text = text.trim_start_matches('#');
+    let value = u32::from_str_radix(text, 16)
+        .ok()
+        .filter(|_| text.len() == 6)
+        .ok_or(format!("Bad colour {}, expected rrggbb", text))?;
+    let [_, r, g, b] = value.to_be_bytes();
+    return Ok([r, g, b]);
+}
+
+// "<background>,<foreground>" as hex colours, e.g. "000000,ffffff"
+impl FromStr for Palette {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Palette, String> {
+        match text.split_once(',') {
+            Some((background, foreground)) => {
+                return Ok(Palette {
+                    background: parse_rgb(background)?,
+                    foreground: parse_rgb(foreground)?,
+                })
+            }
+            None => return Err(format!("Bad palette {}, expected two colours", text)),
+        }
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b] = self.background;
+        let [r2, g2, b2] = self.foreground;
+        return write!(f, "{:02x}{:02x}{:02x},{:02x}{:02x}{:02x}", r, g, b, r2, g2, b2);
+    }
+}
@@ -0,0 +1,43 @@
+use super::ascii::{Display, HEIGHT, WIDTH};
+
+// Keeps cleared pixels glowing for a few frames, like the slow phosphor of old CRTs, so that
+// sprites erased and redrawn with XOR don't flicker. It only changes what is shown: the
+// framebuffer the program draws into is untouched
+pub struct Phosphor {
+    frames: u32,
+    // Frames since each pixel was last lit, 0 while it is lit
+    age: [[u32; WIDTH]; HEIGHT],
+}
+
+pub fn create_phosphor(frames: u32) -> Phosphor {
+    return Phosphor {
+        frames,
+        age: [[frames + 1; WIDTH]; HEIGHT],
+    };
+}
+
+impl Phosphor {
+    // Called once at the end of every frame. Returns whether any pixel is still fading, and so
+    // the screen has to be presented again even if the display didn't change
+    pub fn update(&mut self, display: &Display) -> bool {
+        let mut fading = false;
+        for i in 0..HEIGHT {
+            for j in 0..WIDTH {
+                let age = &mut self.age[i][j];
+                if display.get(i, j) {
+                    *age = 0;
+                } else if *age <= self.frames {
+                    *age += 1;
+                    fading = true;
+                }
+            }
+        }
+        return fading;
+    }
+
+    // Brightness from 1 for a lit pixel down to 0 once it has faded out
+    pub fn level(&self, i: usize, j: usize) -> f64 {
+        let age = self.age[i][j].min(self.frames + 1);
+        return (self.frames + 1 - age) as f64 / (self.frames + 1) as f64;
+    }
+}
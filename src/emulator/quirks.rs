@@ -0,0 +1,179 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Instructions whose behaviour differs between interpreters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    // 8XY1, 8XY2 and 8XY3 clear VF
+    pub vf_reset: bool,
+    // 8XY6 and 8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    // FX55 and FX65 leave I pointing past the last register
+    pub load_store_increments_i: bool,
+    // BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+    // Sprites crossing the edge of the screen wrap around instead of being clipped
+    pub wrap_sprites: bool,
+    // FX1E sets VF when I goes past 0xFFF
+    pub index_overflow_sets_vf: bool,
+    // DXYN waits for the vertical blank, so nothing else runs in the frame after a draw
+    pub display_wait: bool,
+    // I and memory addresses are 16 bits wide instead of 12
+    pub wide_addresses: bool,
+    // F002 loads an audio pattern and FX3A sets its pitch, instead of being unknown opcodes
+    pub audio_patterns: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Profile {
+    // What this emulator has always done
+    Modern,
+    // The original COSMAC VIP interpreter
+    Vip,
+    // SUPER-CHIP 1.1 on the HP 48
+    Schip,
+    // Octo's XO-CHIP
+    XoChip,
+}
+
+impl Quirks {
+    // Addresses wrap at 0xFFF, or at 0xFFFF with wide addresses
+    pub fn address_mask(&self) -> usize {
+        if self.wide_addresses {
+            return 0xFFFF;
+        }
+        return 0xFFF;
+    }
+}
+
+impl Profile {
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Profile::Modern => {
+                return Quirks {
+                    vf_reset: false,
+                    shift_uses_vy: false,
+                    load_store_increments_i: false,
+                    jump_uses_vx: false,
+                    wrap_sprites: false,
+                    index_overflow_sets_vf: true,
+                    display_wait: false,
+                    wide_addresses: true,
+                    audio_patterns: false,
+                }
+            }
+            Profile::Vip => {
+                return Quirks {
+                    vf_reset: true,
+                    shift_uses_vy: true,
+                    load_store_increments_i: true,
+                    jump_uses_vx: false,
+                    wrap_sprites: false,
+                    index_overflow_sets_vf: false,
+                    display_wait: true,
+                    wide_addresses: false,
+                    audio_patterns: false,
+                }
+            }
+            Profile::Schip => {
+                return Quirks {
+                    vf_reset: false,
+                    shift_uses_vy: false,
+                    load_store_increments_i: false,
+                    jump_uses_vx: true,
+                    wrap_sprites: false,
+                    index_overflow_sets_vf: false,
+                    display_wait: false,
+                    wide_addresses: false,
+                    audio_patterns: false,
+                }
+            }
+            Profile::XoChip => {
+                return Quirks {
+                    vf_reset: false,
+                    shift_uses_vy: false,
+                    load_store_increments_i: true,
+                    jump_uses_vx: false,
+                    wrap_sprites: true,
+                    index_overflow_sets_vf: false,
+                    display_wait: false,
+                    wide_addresses: true,
+                    audio_patterns: true,
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Profile, String> {
+        match text {
+            "modern" => return Ok(Profile::Modern),
+            "vip" => return Ok(Profile::Vip),
+            "schip" => return Ok(Profile::Schip),
+            "xochip" => return Ok(Profile::XoChip),
+            _ => return Err(format!("Unknown quirk profile {}", text)),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Profile::Modern => return write!(f, "modern"),
+            Profile::Vip => return write!(f, "vip"),
+            Profile::Schip => return write!(f, "schip"),
+            Profile::XoChip => return write!(f, "xochip"),
+        }
+    }
+}
+
+// Guesses the platform a ROM was written for from the opcodes reachable from its start,
+// following jumps, calls and both sides of skips. Data is never decoded, so sprites that happen
+// to look like extended opcodes don't count. CHIP-8 programs get the VIP's quirks
+pub fn detect(rom: &[u8], load_address: usize) -> Profile {
+    let fetch = |address: usize| -> Option<u16> {
+        let offset = address.checked_sub(load_address)?;
+        let bytes = rom.get(offset..offset + 2)?;
+        return Some((bytes[0] as u16) << 8 | bytes[1] as u16);
+    };
+    let mut profile = Profile::Vip;
+    let mut seen = vec![false; rom.len()];
+    let mut pending = vec![load_address];
+    while let Some(address) = pending.pop() {
+        let Some(inst) = fetch(address) else {
+            continue;
+        };
+        let offset = address - load_address;
+        if seen[offset] {
+            continue;
+        }
+        seen[offset] = true;
+        let (nn, nnn) = (inst & 0xFF, inst & 0xFFF);
+        let mut next = Some(address + 2);
+        match inst >> 12 {
+            0x0 if nnn == 0x0EE || nnn == 0x0FD => next = None,
+            0x0 if (nnn & 0xFF0) == 0x0D0 => return Profile::XoChip,
+            0x0 if (nnn & 0xFF0) == 0x0C0 || (0x0FB..=0x0FF).contains(&nnn) => {
+                profile = Profile::Schip
+            }
+            0x1 => next = Some(nnn as usize),
+            0x2 => pending.push(nnn as usize),
+            0x3 | 0x4 | 0x9 | 0xE => pending.push(address + 4),
+            0x5 if inst & 0xF == 0 => pending.push(address + 4),
+            0x5 if inst & 0xF == 2 || inst & 0xF == 3 => return Profile::XoChip,
+            0xB => next = None,
+            0xD if inst & 0xF == 0 => profile = Profile::Schip,
+            0xF if inst == 0xF000 || nn == 0x01 || nn == 0x02 || nn == 0x3A => {
+                return Profile::XoChip
+            }
+            0xF if nn == 0x30 || nn == 0x75 || nn == 0x85 => profile = Profile::Schip,
+            _ => {}
+        }
+        if let Some(next) = next {
+            pending.push(next);
+        }
+    }
+    return profile;
+}
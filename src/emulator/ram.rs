@@ -1,30 +1,82 @@
-const N_INSTRUCTIONS: usize = 2048 * 2; // Some games want more than original
-const N_BYTES: usize = 2 * N_INSTRUCTIONS;
+use super::font::{self, Font};
+use std::fmt;
+
+const MIN_BYTES: usize = 0x1000; // COSMAC VIP
+const MAX_BYTES: usize = 0x10000; // XO-CHIP
+const N_BYTES: usize = 0x2000; // Some games want more than original
 
 pub const FONT_POS: usize = 0x50;
 pub const PROG_MEM_START: usize = 0x200;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub size: usize,
+    // Where the program is loaded and execution starts, 0x600 on the ETI-660
+    pub load_address: usize,
+    pub font_address: usize,
+}
+
+pub fn default_layout() -> Layout {
+    return Layout {
+        size: N_BYTES,
+        load_address: PROG_MEM_START,
+        font_address: FONT_POS,
+    };
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(
+            f,
+            "{:x} {:x} {:x}",
+            self.size, self.load_address, self.font_address
+        );
+    }
+}
+
 pub struct Ram {
-    mem: [u8; N_BYTES], // Handling 16 bit uints ensures alignment for most cases
+    mem: Vec<u8>,
     iterator: usize,
+    layout: Layout,
+    // The last address the platform can reach, at most the top of ram
+    mask: usize,
 }
 
 impl Ram {
+    // Addresses past the end of what the platform can reach wrap around to the start of ram, as on
+    // a machine whose address lines stop at the top of memory
+    pub fn wrap(&self, address: usize) -> usize {
+        return address & self.mask;
+    }
+    pub fn contains(&self, address: usize) -> bool {
+        return address <= self.mask;
+    }
+    // The number of bytes the platform can reach: ram beyond 4K is out of reach of 12-bit addresses
+    pub fn addressable(&self) -> usize {
+        return self.mask + 1;
+    }
+    // For a movie made with another profile
+    pub fn set_address_mask(&mut self, mask: usize) {
+        self.mask = mask & (self.mem.len() - 1);
+    }
     pub fn fetch(&mut self) -> u16 {
-        return ((self.mem[self.iterator] as u16) << 8) | self.mem[self.iterator + 1] as u16;
+        return ((self.get(self.iterator) as u16) << 8) | self.get(self.iterator + 1) as u16;
     }
     pub fn set_short(&mut self, val: u16) -> () {
-        self.mem[self.iterator] = (val >> 8) as u8;
-        self.mem[self.iterator + 1] = val as u8;
+        let (high, low) = (self.wrap(self.iterator), self.wrap(self.iterator + 1));
+        self.mem[high] = (val >> 8) as u8;
+        self.mem[low] = val as u8;
     }
     pub fn set_byte(&mut self, val: u8) -> () {
-        self.mem[self.iterator] = val;
+        let address = self.wrap(self.iterator);
+        self.mem[address] = val;
     }
+    // The iterator may step past the end of ram, so that running off it can be detected
     pub fn increment(&mut self) -> () {
         self.iterator += 2;
     }
     pub fn decrement(&mut self) -> () {
-        self.iterator -= 2;
+        self.iterator = self.wrap(self.iterator.wrapping_sub(2));
     }
     pub fn jump_to(&mut self, address: usize) -> () {
         self.iterator = address;
@@ -32,41 +84,55 @@ impl Ram {
     pub fn address(&self) -> usize {
         return self.iterator;
     }
-    pub fn get(&mut self, address: usize) -> u8 {
-        return self.mem[address] as u8;
+    pub fn get(&self, address: usize) -> u8 {
+        return self.mem[self.wrap(address)];
+    }
+    pub fn bytes(&self) -> &[u8] {
+        return &self.mem;
+    }
+    pub fn layout(&self) -> Layout {
+        return self.layout;
+    }
+    pub fn font_address(&self) -> usize {
+        return self.layout.font_address;
+    }
+    pub fn big_font_address(&self) -> usize {
+        return self.wrap(self.layout.font_address + font::SMALL_BYTES);
     }
     pub fn get_pgrm_mem(&mut self) -> &mut [u8] {
-        return &mut self.mem[PROG_MEM_START..N_INSTRUCTIONS];
+        return &mut self.mem[self.layout.load_address..];
     }
 }
-const FONT: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
 
-pub fn create_ram() -> Ram {
+// The size is rounded up to a power of two between 4K and 64K, so that addresses wrap with a mask.
+// Addresses past the mask wrap as well, so ram beyond 4K is out of reach of 12-bit addresses. The
+// font has to fit below the program, which may run up to the end of ram
+pub fn create_ram(layout: Layout, font: &Font, address_mask: usize) -> Result<Ram, String> {
+    let size = layout.size.clamp(MIN_BYTES, MAX_BYTES).next_power_of_two();
+    let mask = address_mask & (size - 1);
+    let font_end = layout.font_address + font.small.len() + font.big.len();
+    if layout.load_address > mask {
+        return Err(format!(
+            "The load address {:#x} is past the last address, {:#x}",
+            layout.load_address, mask
+        ));
+    }
+    if font_end > layout.load_address {
+        return Err(format!(
+            "The font at {:#x}..{:#x} overlaps the program loaded at {:#x}",
+            layout.font_address, font_end, layout.load_address
+        ));
+    }
     let mut ram = Ram {
-        mem: [0; N_BYTES],
-        iterator: FONT_POS,
+        mem: vec![0; size],
+        iterator: layout.font_address,
+        layout: Layout { size, ..layout },
+        mask,
     };
-    for i in 0..FONT.len() {
-        ram.jump_to(FONT_POS + i);
-        ram.set_byte(FONT[i]);
+    for (i, byte) in font.small.iter().chain(font.big.iter()).enumerate() {
+        ram.jump_to(layout.font_address + i);
+        ram.set_byte(*byte);
     }
-    ram.jump_to(PROG_MEM_START);
-    return ram;
+    ram.jump_to(layout.load_address);
+    return Ok(ram);
 }
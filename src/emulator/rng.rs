@@ -0,0 +1,142 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Source of CXNN bytes. Its text form is the complete state, so a run can be saved and resumed
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rng {
+    // xorshift64*
+    Xorshift { state: u64 },
+    // The VIP interpreter kept its seed in R9 and advanced it on every fetch,
+    // adding the high and low halves together for each random byte
+    Vip { seed: u16 },
+    // Repeats the given bytes, for tests
+    Fixed { bytes: Vec<u8>, position: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mode {
+    Xorshift,
+    Vip,
+    Fixed(Vec<u8>),
+}
+
+fn splitmix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    return z ^ (z >> 31);
+}
+
+pub fn create(mode: Mode, seed: u64) -> Rng {
+    match mode {
+        // xorshift never leaves the zero state
+        Mode::Xorshift => return Rng::Xorshift { state: splitmix(seed) | 1 },
+        Mode::Vip => return Rng::Vip { seed: seed as u16 },
+        Mode::Fixed(bytes) => return Rng::Fixed { bytes, position: 0 },
+    }
+}
+
+impl Rng {
+    pub fn next_byte(&mut self) -> u8 {
+        match self {
+            Rng::Xorshift { state } => {
+                *state ^= *state >> 12;
+                *state ^= *state << 25;
+                *state ^= *state >> 27;
+                return (state.wrapping_mul(0x2545F4914F6CDD1D) >> 56) as u8;
+            }
+            Rng::Vip { seed } => {
+                let [hi, lo] = seed.to_be_bytes();
+                let val = hi.wrapping_add(lo);
+                *seed = u16::from_be_bytes([val, lo.wrapping_add(1)]);
+                return val;
+            }
+            Rng::Fixed { bytes, position } => {
+                if bytes.is_empty() {
+                    return 0;
+                }
+                let val = bytes[*position % bytes.len()];
+                *position = (*position + 1) % bytes.len();
+                return val;
+            }
+        }
+    }
+
+    // Called once per fetched instruction
+    pub fn clock(&mut self) {
+        if let Rng::Vip { seed } = self {
+            *seed = seed.wrapping_add(1);
+        }
+    }
+}
+
+fn parse_bytes(text: &str) -> Result<Vec<u8>, String> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    return text
+        .split(',')
+        .map(|byte| u8::from_str_radix(byte.trim_start_matches("0x"), 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|e| format!("Bad byte list {}: {}", text, e));
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    return bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(",");
+}
+
+// "xorshift", "vip" or "fixed:01,02,ff"
+impl FromStr for Mode {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Mode, String> {
+        match text.split_once(':') {
+            None if text == "xorshift" => return Ok(Mode::Xorshift),
+            None if text == "vip" => return Ok(Mode::Vip),
+            Some(("fixed", bytes)) => return Ok(Mode::Fixed(parse_bytes(bytes)?)),
+            _ => return Err(format!("Unknown rng mode {}", text)),
+        }
+    }
+}
+
+impl fmt::Display for Rng {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rng::Xorshift { state } => return write!(f, "xorshift:{:016x}", state),
+            Rng::Vip { seed } => return write!(f, "vip:{:04x}", seed),
+            Rng::Fixed { bytes, position } => {
+                return write!(f, "fixed:{}:{}", position, format_bytes(bytes))
+            }
+        }
+    }
+}
+
+impl FromStr for Rng {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Rng, String> {
+        let bad = |_| format!("Bad rng state {}", text);
+        let parts: Vec<&str> = text.splitn(3, ':').collect();
+        match parts[..] {
+            ["xorshift", state] => {
+                return Ok(Rng::Xorshift {
+                    state: u64::from_str_radix(state, 16).map_err(bad)?,
+                })
+            }
+            ["vip", seed] => {
+                return Ok(Rng::Vip {
+                    seed: u16::from_str_radix(seed, 16).map_err(bad)?,
+                })
+            }
+            ["fixed", position, bytes] => {
+                return Ok(Rng::Fixed {
+                    bytes: parse_bytes(bytes)?,
+                    position: position.parse().map_err(bad)?,
+                })
+            }
+            _ => return Err(format!("Bad rng state {}", text)),
+        }
+    }
+}
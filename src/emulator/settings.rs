@@ -0,0 +1,164 @@
+use super::fnv;
+use super::keymap::Keymap;
+use super::palette::Palette;
+use super::quirks::Profile;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+// What a game can set for itself. Anything left out comes from the next place looked in:
+// command line flags, then the sidecar file next to the ROM, then the ROM's entry in
+// roms.toml, then the defaults in roms.toml.
+// The palette is the one of screenshots, video and terminal graphics, the game's own pictures.
+// The terminal theme and --colors are left out: they are how the player likes their terminal,
+// not something a game needs
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Settings {
+    pub instructions_per_frame: Option<u64>,
+    pub quirks: Option<Profile>,
+    pub keymap: Option<Keymap>,
+    pub palette: Option<Palette>,
+}
+
+// A table of settings in a file, with the same names and values as the command line flags
+#[derive(Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Table {
+    ipf: Option<u64>,
+    quirks: Option<String>,
+    keymap: Option<String>,
+    palette: Option<String>,
+}
+
+// roms.toml: [defaults] for every game, and [roms.<hash>] for one
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    defaults: Table,
+    #[serde(default)]
+    roms: HashMap<String, Table>,
+}
+
+#[derive(Default)]
+pub struct Config {
+    pub defaults: Settings,
+    // By rom_hash
+    pub roms: HashMap<String, Settings>,
+}
+
+impl Settings {
+    // These settings, with the ones missing taken from the fallback
+    pub fn or(self, fallback: Settings) -> Settings {
+        return Settings {
+            instructions_per_frame: self
+                .instructions_per_frame
+                .or(fallback.instructions_per_frame),
+            quirks: self.quirks.or(fallback.quirks),
+            keymap: self.keymap.or(fallback.keymap),
+            palette: self.palette.or(fallback.palette),
+        };
+    }
+
+    fn parse(table: Table) -> Result<Settings, String> {
+        return Ok(Settings {
+            instructions_per_frame: table.ipf,
+            quirks: table.quirks.map(|text| text.parse()).transpose()?,
+            keymap: table.keymap.map(|text| text.parse()).transpose()?,
+            palette: table.palette.map(|text| text.parse()).transpose()?,
+        });
+    }
+
+    fn table(&self) -> Table {
+        return Table {
+            ipf: self.instructions_per_frame,
+            quirks: self.quirks.map(|quirks| quirks.to_string()),
+            keymap: self.keymap.as_ref().map(|keymap| keymap.to_string()),
+            palette: self.palette.as_ref().map(|palette| palette.to_string()),
+        };
+    }
+}
+
+// What roms.toml knows a ROM by: FNV-1a of the file as 16 hex digits
+pub fn rom_hash(rom: &[u8]) -> String {
+    let mut hasher = fnv::create();
+    hasher.write(rom);
+    return format!("{:016x}", hasher.finish());
+}
+
+// game.ch8.toml for game.ch8
+pub fn sidecar(rom: &str) -> PathBuf {
+    return PathBuf::from(format!("{}.toml", rom));
+}
+
+// $XDG_CONFIG_HOME/rusty/roms.toml, or ~/.config/rusty/roms.toml
+pub fn config_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    return Some(base.join("rusty").join("roms.toml"));
+}
+
+// The text of a settings file, or None if there isn't one
+fn read(path: &Path) -> Result<Option<String>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => return Ok(Some(text)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+// A sidecar file, empty if there is none
+pub fn load(path: &Path) -> Result<Settings, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let Some(text) = read(path)? else {
+        return Ok(Settings::default());
+    };
+    let table: Table = toml::from_str(&text).map_err(|e| error(&e))?;
+    return Settings::parse(table).map_err(|e| error(&e));
+}
+
+pub fn save(path: &Path, settings: &Settings) -> Result<(), String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let text = toml::to_string(&settings.table()).map_err(|e| error(&e))?;
+    std::fs::write(path, text).map_err(|e| error(&e))?;
+    info!("Saved settings to {}", path.display());
+    return Ok(());
+}
+
+// roms.toml, empty if there is none
+pub fn load_config(path: &Path) -> Result<Config, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let Some(text) = read(path)? else {
+        return Ok(Config::default());
+    };
+    let file: ConfigFile = toml::from_str(&text).map_err(|e| error(&e))?;
+    let mut config = Config {
+        defaults: Settings::parse(file.defaults).map_err(|e| error(&e))?,
+        roms: HashMap::new(),
+    };
+    for (hash, table) in file.roms {
+        let settings = Settings::parse(table).map_err(|e| error(&format!("{}: {}", hash, e)))?;
+        config.roms.insert(hash.to_lowercase(), settings);
+    }
+    return Ok(config);
+}
+
+// The settings of a ROM given the ones from the command line. Whatever is still missing is up
+// to the caller's defaults
+pub fn resolve(given: &Settings, rom: &str, config: &Config) -> Result<Settings, String> {
+    let bytes = std::fs::read(rom).map_err(|e| format!("{}: {}", rom, e))?;
+    let entry = config
+        .roms
+        .get(&rom_hash(&bytes))
+        .cloned()
+        .unwrap_or_default();
+    return Ok(given
+        .clone()
+        .or(load(&sidecar(rom))?)
+        .or(entry)
+        .or(config.defaults.clone()));
+}
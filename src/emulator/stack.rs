@@ -1,4 +1,5 @@
 pub struct Stack {
     pub i: u16,
     pub v: [u8; 16],
+    pub calls: Vec<usize>, // Return addresses
 }
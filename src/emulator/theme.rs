@@ -0,0 +1,297 @@
+use super::palette::Rgb;
+use serde::Deserialize;
+use std::str::FromStr;
+
+// A terminal colour, at whatever depth the terminal supports
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    // Whatever the terminal is already using
+    Default,
+    // One of the 16 ANSI colours, 8-15 being the bright ones
+    Ansi(u8),
+    // The 256 colour palette
+    Indexed(u8),
+    Rgb(Rgb),
+}
+
+const ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+// xterm's values for the first 16 entries of the 256 colour palette
+const XTERM_16: [u32; 16] = [
+    0x000000, 0xCD0000, 0x00CD00, 0xCDCD00, 0x0000EE, 0xCD00CD, 0x00CDCD, 0xE5E5E5, 0x7F7F7F,
+    0xFF0000, 0x00FF00, 0xFFFF00, 0x5C5CFF, 0xFF00FF, 0x00FFFF, 0xFFFFFF,
+];
+// Channel levels of the 6x6x6 colour cube from entry 16 on
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    return (0..3)
+        .map(|k| (a[k] as i32 - b[k] as i32).pow(2) as u32)
+        .sum();
+}
+
+// The closest colour of the cube or of the grey ramp from entry 232 on
+fn nearest_indexed(color: Rgb) -> u8 {
+    let level = |channel: u8| {
+        return (0..6)
+            .min_by_key(|n| (CUBE[*n] as i32 - channel as i32).abs())
+            .unwrap();
+    };
+    let [r, g, b] = color.map(level);
+    let cube = (16 + 36 * r + 6 * g + b) as u8;
+    let mean = color.iter().map(|channel| *channel as u32).sum::<u32>() / 3;
+    let grey = 232 + (mean.saturating_sub(3) / 10).min(23) as u8;
+    let to_rgb = |n: u8| Color::Indexed(n).rgb().unwrap();
+    if distance(to_rgb(grey), color) < distance(to_rgb(cube), color) {
+        return grey;
+    }
+    return cube;
+}
+
+impl Color {
+    // The actual colour where it is known; the 16 ANSI colours are up to the terminal
+    fn rgb(&self) -> Option<Rgb> {
+        match self {
+            Color::Indexed(n) if *n < 16 => return Some(rgb_of(XTERM_16[*n as usize])),
+            Color::Indexed(n) if *n < 232 => {
+                let n = *n - 16;
+                return Some([n / 36, n / 6 % 6, n % 6].map(|level| CUBE[level as usize]));
+            }
+            Color::Indexed(n) => return Some([8 + 10 * (*n - 232); 3]),
+            Color::Rgb(rgb) => return Some(*rgb),
+            _ => return None,
+        }
+    }
+
+    // The colour the given fraction of the way from self to target, at the target's depth. None
+    // where there are no colours in between to pick
+    fn blend(&self, target: Color, level: f64) -> Option<Color> {
+        let (from, to) = (self.rgb()?, target.rgb()?);
+        let mut mixed = [0; 3];
+        for k in 0..3 {
+            mixed[k] = (from[k] as f64 + (to[k] as f64 - from[k] as f64) * level).round() as u8;
+        }
+        match target {
+            Color::Indexed(_) => return Some(Color::Indexed(nearest_indexed(mixed))),
+            _ => return Some(Color::Rgb(mixed)),
+        }
+    }
+
+    // The SGR parameters selecting this colour as foreground or background
+    fn sgr(&self, background: bool) -> String {
+        let layer = if background { 10 } else { 0 };
+        match self {
+            Color::Default => return format!("{}", 39 + layer),
+            Color::Ansi(n) if *n < 8 => return format!("{}", 30 + layer + n),
+            Color::Ansi(n) => return format!("{}", 90 + layer + n - 8),
+            Color::Indexed(n) => return format!("{};5;{}", 38 + layer, n),
+            Color::Rgb([r, g, b]) => return format!("{};2;{};{};{}", 38 + layer, r, g, b),
+        }
+    }
+}
+
+// "default", an ANSI name such as "red" or "bright-red", a number from 0 to 255 for the 256
+// colour palette, or #rrggbb
+impl FromStr for Color {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Color, String> {
+        if text == "default" {
+            return Ok(Color::Default);
+        }
+        let (bright, name) = match text.strip_prefix("bright-") {
+            Some(name) => (8, name),
+            None => (0, text),
+        };
+        if let Some(n) = ANSI_NAMES.iter().position(|ansi| *ansi == name) {
+            return Ok(Color::Ansi(bright + n as u8));
+        }
+        if let Ok(n) = text.parse::<u8>() {
+            return Ok(Color::Indexed(n));
+        }
+        if let Some(hex) = text.strip_prefix('#') {
+            let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6);
+            if let Some(value) = value {
+                let [_, r, g, b] = value.to_be_bytes();
+                return Ok(Color::Rgb([r, g, b]));
+            }
+        }
+        return Err(format!("Unknown colour {}", text));
+    }
+}
+
+// How the terminal renderer draws a pixel, by value: unlit, XO-CHIP's first plane, its second
+// plane and both planes. Until the display has a second plane only the first two are used
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub colors: [Color; 4],
+    pub glyphs: [String; 4],
+}
+
+fn create(colors: [Color; 4], glyphs: [&str; 4]) -> Theme {
+    return Theme {
+        colors,
+        glyphs: glyphs.map(|glyph| glyph.to_string()),
+    };
+}
+
+fn rgb_of(value: u32) -> Rgb {
+    let [_, r, g, b] = value.to_be_bytes();
+    return [r, g, b];
+}
+
+fn rgb(value: u32) -> Color {
+    return Color::Rgb(rgb_of(value));
+}
+
+const BLOCKS: [&str; 4] = ["██"; 4];
+
+pub fn default() -> Theme {
+    return create([Color::Default; 4], ["⬛", "⬜", "⬜", "⬜"]);
+}
+
+pub fn named(name: &str) -> Option<Theme> {
+    match name {
+        "emoji" => return Some(default()),
+        // Octo's default colours
+        "octo" => {
+            return Some(create(
+                [rgb(0x996600), rgb(0xFFCC00), rgb(0xFF6600), rgb(0x662200)],
+                BLOCKS,
+            ))
+        }
+        "lcd" => {
+            return Some(create(
+                [rgb(0x9BBC0F), rgb(0x0F380F), rgb(0x306230), rgb(0x8BAC0F)],
+                BLOCKS,
+            ))
+        }
+        "amber" => {
+            return Some(create(
+                [rgb(0x1A0F00), rgb(0xFFB000), rgb(0xB36B00), rgb(0xFFD27F)],
+                BLOCKS,
+            ))
+        }
+        // The 16 ANSI colours, so that it works on any colour terminal
+        "high-contrast" => {
+            return Some(create(
+                [
+                    Color::Ansi(0),
+                    Color::Ansi(15),
+                    Color::Ansi(11),
+                    Color::Ansi(14),
+                ],
+                BLOCKS,
+            ))
+        }
+        _ => return None,
+    }
+}
+
+// A theme file, in toml. Anything left out comes from the base theme, or else the default
+//   base = "octo"
+//   colors = ["#000000", "bright-green", "2", "default"]
+//   glyphs = ["  ", "██", "▓▓", "▒▒"]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    colors: Option<Vec<String>>,
+    glyphs: Option<Vec<String>>,
+}
+
+pub fn load(path: &str) -> Result<Theme, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let file: ThemeFile = toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let mut theme = match &file.base {
+        Some(base) => named(base).ok_or(format!("{}: unknown base theme {}", path, base))?,
+        None => default(),
+    };
+    if let Some(colors) = &file.colors {
+        theme
+            .set_colors(colors)
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    if let Some(glyphs) = &file.glyphs {
+        theme
+            .set_glyphs(glyphs)
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    return Ok(theme);
+}
+
+// A theme name, or else the path of a theme file
+pub fn find(text: &str) -> Result<Theme, String> {
+    match named(text) {
+        Some(theme) => return Ok(theme),
+        None => return load(text),
+    }
+}
+
+impl Theme {
+    // Replaces the first colours; given only two, the plane colours follow the foreground
+    pub fn set_colors(&mut self, colors: &[String]) -> Result<(), String> {
+        let colors = colors
+            .iter()
+            .map(|color| color.parse())
+            .collect::<Result<Vec<Color>, String>>()?;
+        match colors.len() {
+            2 => self.colors = [colors[0], colors[1], colors[1], colors[1]],
+            4 => self.colors.copy_from_slice(&colors),
+            n => return Err(format!("A theme has 2 or 4 colours, not {}", n)),
+        }
+        return Ok(());
+    }
+
+    pub fn set_glyphs(&mut self, glyphs: &[String]) -> Result<(), String> {
+        match glyphs.len() {
+            2 => self.glyphs = [0, 1, 1, 1].map(|i| glyphs[i].clone()),
+            4 => self.glyphs.clone_from_slice(glyphs),
+            n => return Err(format!("A theme has 2 or 4 glyphs, not {}", n)),
+        }
+        return Ok(());
+    }
+
+    // The escape sequence and glyph for one pixel: the glyph in the pixel's colour on the
+    // unlit colour
+    pub fn pixel(&self, value: usize) -> String {
+        if self.plain() {
+            return self.glyphs[value].clone();
+        }
+        return format!(
+            "\x1B[{};{}m{}",
+            self.colors[value].sgr(false),
+            self.colors[0].sgr(true),
+            self.glyphs[value]
+        );
+    }
+
+    // A pixel cleared a few frames ago, at the given brightness between 0 and 1. Themes without
+    // colours in between, such as the 16 ANSI ones, show it fully lit until it goes out
+    pub fn faded(&self, level: f64) -> String {
+        if level <= 0.0 {
+            return self.pixel(0);
+        }
+        match self.colors[0].blend(self.colors[1], level) {
+            Some(color) => {
+                return format!(
+                    "\x1B[{};{}m{}",
+                    color.sgr(false),
+                    self.colors[0].sgr(true),
+                    self.glyphs[1]
+                )
+            }
+            None => return self.pixel(1),
+        }
+    }
+
+    // Ends a row, so that the colour doesn't run into the rest of the line
+    pub fn reset(&self) -> &'static str {
+        return if self.plain() { "" } else { "\x1B[0m" };
+    }
+
+    fn plain(&self) -> bool {
+        return self.colors.iter().all(|color| *color == Color::Default);
+    }
+}
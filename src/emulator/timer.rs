@@ -1,48 +1,43 @@
-use std::{thread, time};
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub struct Timer<T: FnMut() -> bool> {
-    pub interval: time::Duration,
-    pub action: T,
+const MAX_LAG_FRAMES: u32 = 5;
+
+// Paces frames against fixed deadlines rather than sleeping a fixed time after each one, so time
+// spent emulating or oversleeping doesn't accumulate into drift
+pub struct Scheduler {
+    interval: Duration,
+    deadline: Instant,
+}
+
+pub fn create_scheduler(interval: Duration) -> Scheduler {
+    return Scheduler {
+        interval,
+        deadline: Instant::now(),
+    };
 }
 
-impl<T: FnMut() -> bool> Timer<T> {
-    pub fn run(&mut self) -> () {
-        loop {
-            if !(self.action)() {
-                return;
-            }
-            thread::sleep(self.interval)
+impl Scheduler {
+    // Sleeps until the next frame is due, running at speed times the normal rate
+    pub fn wait(&mut self, speed: f64) {
+        self.deadline += self.interval.div_f64(speed);
+        let now = Instant::now();
+        if self.deadline > now {
+            thread::sleep(self.deadline - now);
+        } else if now - self.deadline > self.interval * MAX_LAG_FRAMES {
+            // Too far behind to catch up without a burst of frames, e.g. after being suspended
+            self.deadline = now;
         }
     }
 }
 
-use std::sync::Arc;
-use atomic_counter::{RelaxedCounter, AtomicCounter};
-
-pub struct ThreadedCounter {
-    pub counter: Arc<atomic_counter::RelaxedCounter>,
-    pub handle: std::thread::JoinHandle<()>,
+// Delay and sound timers, decremented once per frame
+pub struct Countdown {
+    pub value: u8,
 }
 
-pub fn create_threaded_counter(interval: time::Duration) -> ThreadedCounter {
-    let val = Arc::new(RelaxedCounter::new(255));
-
-    let val_clone = val.clone();
-    let mut timer = Timer {
-        interval,
-        action: move || {
-            if (*val_clone).get() < 255 {
-                val_clone.inc();
-                return true;
-            }
-            else {
-                return (val_clone).get() < 256;
-            }
-        },
-    };
-    let handle = std::thread::spawn(move || timer.run());
-    return ThreadedCounter {
-        counter: val.clone(),
-        handle,
-    };
+impl Countdown {
+    pub fn tick(&mut self) {
+        self.value = self.value.saturating_sub(1);
+    }
 }
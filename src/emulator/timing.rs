@@ -0,0 +1,84 @@
+use super::instruction::Instruction;
+use std::fmt;
+use std::str::FromStr;
+
+// How long a frame is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    // A fixed number of instructions per frame
+    Fixed,
+    // Instructions cost what they took on the COSMAC VIP, with the frame limited to the machine
+    // cycles left over by the display
+    Vip,
+}
+
+// 1.7609 MHz, 8 clocks per machine cycle, 60 frames per second
+const VIP_CYCLES_PER_FRAME: u64 = 3668;
+// The 1861 takes one cycle per displayed byte by DMA, 128 lines of 8 bytes, and the interrupt
+// routine that sets it up and counts down the timers runs in the vertical blank
+const VIP_DISPLAY_DMA: u64 = 1024;
+const VIP_INTERRUPT: u64 = 30;
+pub const VIP_FRAME_BUDGET: u64 = VIP_CYCLES_PER_FRAME - VIP_DISPLAY_DMA - VIP_INTERRUPT;
+
+// Fetching and dispatching, paid by every instruction
+const VIP_FETCH: u64 = 40;
+
+// Machine cycles taken by an instruction on the VIP, estimated from the interpreter's 1802
+// routines at 2 cycles per 1802 instruction and not measured on real hardware. vx is the value of
+// VX before executing it
+pub fn vip_cycles(inst: u16, vx: u8, skipped: bool) -> u64 {
+    let skip = if skipped { 4 } else { 0 };
+    let cost = match inst.instruction_of() {
+        0x0 if inst.jump_addr() == 0x0E0 => 24 + 6 * 256, // A loop over the 256 display bytes
+        0x0 => 10,
+        0x1 => 12,
+        0x2 => 26,
+        0x3 | 0x4 => 10 + skip,
+        0x5 | 0x9 => 14 + skip,
+        0x6 => 6,
+        0x7 => 10,
+        0x8 => 44, // Executed through a patched 1802 instruction, so the same for every ALU op
+        0xA => 12,
+        0xB => 22,
+        0xC => 36,
+        0xD => draw_cycles(inst.fourth_nibble_of() as u64, vx),
+        0xE => 14 + skip,
+        0xF => match inst.second_byte_of() {
+            // Repeated subtraction, so every unit of every digit costs a pass
+            0x33 => 24 + 16 * (vx / 100 + vx / 10 % 10 + vx % 10) as u64,
+            0x55 | 0x65 => 14 + 14 * (inst.x_register_of() as u64 + 1),
+            0x1E | 0x29 | 0x30 => 16,
+            _ => 10,
+        },
+        _ => 10,
+    };
+    return VIP_FETCH + cost;
+}
+
+// Every sprite row is shifted into place one bit at a time, and rows that aren't byte aligned
+// are written to two display bytes instead of one
+fn draw_cycles(rows: u64, vx: u8) -> u64 {
+    let shift = (vx % 8) as u64;
+    let row = if shift == 0 { 28 } else { 44 + 4 * shift };
+    return 26 + rows * row;
+}
+
+impl FromStr for Mode {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Mode, String> {
+        match text {
+            "fixed" => return Ok(Mode::Fixed),
+            "vip" => return Ok(Mode::Vip),
+            _ => return Err(format!("Unknown timing mode {}", text)),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Fixed => return write!(f, "fixed"),
+            Mode::Vip => return write!(f, "vip"),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use super::ascii::Display;
+use super::orientation::Orientation;
+use super::palette::Palette;
+use log::{error, info};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+const FRAME_RATE: u64 = 60;
+
+enum Output {
+    // Only frames that differ from the one before, shown for as long as the screen didn't change
+    Gif(gif::Encoder<BufWriter<File>>),
+    // Packed rgb24 for every frame, e.g. for
+    //   ffmpeg -f rawvideo -pix_fmt rgb24 -s 256x128 -r 60 -i - out.mp4
+    Raw(Box<dyn Write>),
+}
+
+pub struct Video {
+    output: Output,
+    turn: Orientation,
+    scale: usize,
+    palette: Palette,
+    // The gif frame not written yet, since its delay is only known once the screen changes
+    pending: Option<Vec<u8>>,
+    frames: u64,
+    centiseconds: u64, // Written so far; gif delays are in hundredths of a second
+}
+
+// A .gif, or raw video to any other path, or to stdout for "-"
+pub fn create(
+    path: &str,
+    turn: Orientation,
+    scale: usize,
+    palette: Palette,
+) -> Result<Video, String> {
+    let scale = scale.max(1);
+    let (height, width) = turn.size();
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path, e);
+    let output = if path == "-" {
+        Output::Raw(Box::new(std::io::stdout()))
+    } else if path.ends_with(".gif") {
+        let file = BufWriter::new(File::create(path).map_err(|e| error(&e))?);
+        let colors = [palette.background, palette.foreground].concat();
+        // Gif sizes are 16 bits
+        let fit = |size: usize| size.checked_mul(scale).and_then(|size| u16::try_from(size).ok());
+        let (Some(width), Some(height)) = (fit(width), fit(height)) else {
+            return Err(format!("{}: scale {} is too large for a gif", path, scale));
+        };
+        let mut encoder = gif::Encoder::new(file, width, height, &colors).map_err(|e| error(&e))?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| error(&e))?;
+        Output::Gif(encoder)
+    } else {
+        Output::Raw(Box::new(BufWriter::new(
+            File::create(path).map_err(|e| error(&e))?,
+        )))
+    };
+    info!("Recording video to {}", path);
+    return Ok(Video {
+        output,
+        turn,
+        scale,
+        palette,
+        pending: None,
+        frames: 0,
+        centiseconds: 0,
+    });
+}
+
+impl Video {
+    // One entry per scaled pixel: palette indices for gif, rgb triples for raw video
+    fn pixels(&self, display: &Display, indexed: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        let (height, width) = self.turn.size();
+        for i in 0..height * self.scale {
+            for j in 0..width * self.scale {
+                let lit = self.turn.get(display, i / self.scale, j / self.scale);
+                if indexed {
+                    out.push(lit as u8);
+                } else {
+                    out.extend_from_slice(&self.palette.color(lit));
+                }
+            }
+        }
+        return out;
+    }
+
+    // Called once per emulated frame
+    pub fn frame(&mut self, display: &Display) {
+        let indexed = matches!(self.output, Output::Gif(_));
+        let pixels = self.pixels(display, indexed);
+        let result = match &mut self.output {
+            Output::Raw(out) => out.write_all(&pixels),
+            Output::Gif(_) => {
+                if self.pending.as_ref() != Some(&pixels) {
+                    self.write_pending();
+                    self.pending = Some(pixels);
+                }
+                Ok(())
+            }
+        };
+        self.frames += 1;
+        if let Err(e) = result {
+            error!("Video write failed: {}", e);
+        }
+    }
+
+    fn write_pending(&mut self) {
+        let Some(pixels) = self.pending.take() else {
+            return;
+        };
+        // Rounding the running total keeps the timing right over many frames
+        let total = (self.frames * 100 + FRAME_RATE / 2) / FRAME_RATE;
+        let delay = total - self.centiseconds;
+        self.centiseconds = total;
+        let (height, width) = self.turn.size();
+        let (width, height) = ((width * self.scale) as u16, (height * self.scale) as u16);
+        let mut frame = gif::Frame::from_indexed_pixels(width, height, pixels, None);
+        frame.delay = delay.min(u16::MAX as u64) as u16;
+        if let Output::Gif(encoder) = &mut self.output {
+            if let Err(e) = encoder.write_frame(&frame) {
+                error!("Video write failed: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for Video {
+    fn drop(&mut self) {
+        self.write_pending();
+        if let Output::Raw(out) = &mut self.output {
+            let _ = out.flush();
+        }
+        info!("Recorded {} frames of video", self.frames);
+    }
+}
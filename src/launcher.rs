@@ -0,0 +1,158 @@
+use crate::cli::RunArgs;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use rusty::emulator::quirks::Profile;
+use rusty::roms::{self, Entry};
+use std::path::Path;
+use std::time::Duration;
+
+fn platform(profile: Profile) -> &'static str {
+    match profile {
+        Profile::Schip => return "SUPER-CHIP",
+        Profile::XoChip => return "XO-CHIP",
+        _ => return "CHIP-8",
+    }
+}
+
+struct Launcher {
+    entries: Vec<Entry>,
+    selected: usize,
+    status: String,
+    // The entry played last, for saving with s
+    played: Option<usize>,
+}
+
+// Lists the ROMs under the directory and runs the one picked with the profile detected for it,
+// coming back to the list when it ends or the player quits
+pub fn run(args: &RunArgs) -> Result<(), String> {
+    let entries = roms::scan(Path::new(&args.rom));
+    if entries.is_empty() {
+        return Err(format!("No ROMs under {}", args.rom));
+    }
+    let mut launcher = Launcher {
+        entries,
+        selected: 0,
+        status: String::new(),
+        played: None,
+    };
+    loop {
+        let mut terminal = ratatui::init();
+        // Keys pressed during the game are still waiting to be read, and would quit the list
+        // or start another ROM
+        while event::poll(Duration::ZERO).map_err(|e| e.to_string())? {
+            event::read().map_err(|e| e.to_string())?;
+        }
+        let picked = launcher.pick(&mut terminal, args);
+        ratatui::restore();
+        let picked = match picked? {
+            Some(picked) => picked,
+            None => return Ok(()),
+        };
+        let entry = &launcher.entries[picked];
+        let result = crate::play(args, &entry.path.to_string_lossy(), entry.profile);
+        launcher.status = match result {
+            Ok(()) => String::new(),
+            Err(e) => format!("{}: {}", entry.name, e),
+        };
+        launcher.played = Some(picked);
+    }
+}
+
+impl Launcher {
+    // The index of the entry picked, or None to quit
+    fn pick(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        args: &RunArgs,
+    ) -> Result<Option<usize>, String> {
+        let last = self.entries.len() - 1;
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(|e| e.to_string())?;
+            let Event::Key(key) = event::read().map_err(|e| e.to_string())? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                KeyCode::Enter => return Ok(Some(self.selected)),
+                KeyCode::Char('s') => self.save(args),
+                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down => self.selected = (self.selected + 1).min(last),
+                KeyCode::PageUp => self.selected = self.selected.saturating_sub(20),
+                KeyCode::PageDown => self.selected = (self.selected + 20).min(last),
+                KeyCode::Home => self.selected = 0,
+                KeyCode::End => self.selected = last,
+                _ => {}
+            }
+        }
+    }
+
+    // Saves the settings given as flags for the last game, whichever ROM is selected
+    fn save(&mut self, args: &RunArgs) {
+        let Some(played) = self.played else {
+            self.status = "No game played yet".to_string();
+            return;
+        };
+        let entry = &self.entries[played];
+        self.status = match crate::save_settings(args, &entry.path.to_string_lossy()) {
+            Ok(()) => format!("Saved the settings of {}", entry.name),
+            Err(e) => e,
+        };
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [list, notes] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Min(0)]).areas(main);
+
+        // A heading line before the first ROM of each category
+        let mut items = Vec::new();
+        let mut row = 0;
+        for (n, entry) in self.entries.iter().enumerate() {
+            if n == 0 || self.entries[n - 1].category != entry.category {
+                let style = Style::default().add_modifier(Modifier::BOLD);
+                items.push(ListItem::new(Line::styled(entry.category.clone(), style)));
+            }
+            if n == self.selected {
+                row = items.len();
+            }
+            items.push(ListItem::new(format!("  {}", entry.name)));
+        }
+        let mut state = ListState::default().with_selected(Some(row));
+        let roms = List::new(items)
+            .block(Block::bordered().title("ROMs"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(roms, list, &mut state);
+
+        let entry = &self.entries[self.selected];
+        let mut text = vec![
+            Line::from(entry.path.to_string_lossy().to_string()),
+            Line::from(format!(
+                "{}, runs with the {} profile",
+                platform(entry.profile),
+                entry.profile
+            )),
+            Line::from(""),
+        ];
+        match &entry.notes {
+            Some(notes) => text.extend(notes.lines().map(|line| Line::from(line.to_string()))),
+            None => text.push(Line::from("No notes")),
+        }
+        let details = Paragraph::new(text)
+            .block(Block::bordered().title(entry.name.clone()))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(details, notes);
+
+        let line = format!("{} | Enter play  s save settings  Esc quit", self.status);
+        frame.render_widget(Paragraph::new(line), help);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod emulator;
+pub mod from_file;
+pub mod roms;
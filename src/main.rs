@@ -1,11 +1,102 @@
-mod emulator;
-mod from_file;
+mod bench;
+mod cli;
+mod launcher;
+mod render;
+mod tui;
+use clap::{CommandFactory, FromArgMatches};
+use rusty::emulator::{self, quirks, settings};
+use rusty::from_file;
 use env_logger;
 
 fn main() {
     env_logger::init();
     log::info!("Logging on");
-    let mut emul = emulator::create_emulator();
-    from_file::read("prog.ch8", emul.get_pgrm_mem());
-    emul.run();
+    let matches = cli::Cli::command().get_matches();
+    let mut cli = cli::Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    cli.run.read_given(&matches);
+    match cli.command {
+        Some(cli::Command::Bench(args)) => bench::run(&args),
+        Some(cli::Command::Render(args)) => render::run(&args),
+        None => {
+            let result = if std::path::Path::new(&cli.run.rom).is_dir() {
+                launcher::run(&cli.run)
+            } else {
+                play(&cli.run, &cli.run.rom, cli.run.quirks).and_then(|()| {
+                    if cli.run.save_settings {
+                        save_settings(&cli.run, &cli.run.rom)?;
+                    }
+                    return Ok(());
+                })
+            };
+            if let Err(e) = result {
+                log::error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// Writes the settings given as flags into the ROM's sidecar file, keeping what it already has.
+// Anything left to the defaults isn't written, so roms.toml still applies to it
+pub fn save_settings(args: &cli::RunArgs, rom: &str) -> Result<(), String> {
+    let path = settings::sidecar(rom);
+    let saved = settings::load(&path)?;
+    return settings::save(&path, &args.given.clone().or(saved));
+}
+
+// Runs a ROM interactively until it ends or the player quits. The quirk profile is used unless
+// the flags or settings files choose one
+pub fn play(args: &cli::RunArgs, rom: &str, quirks: quirks::Profile) -> Result<(), String> {
+    if args.video.record_video.as_deref() == Some("-") {
+        return Err("Raw video goes to stdout only with the render command".to_string());
+    }
+    let config = match settings::config_path() {
+        Some(path) => settings::load_config(&path)?,
+        None => settings::Config::default(),
+    };
+    let found = settings::resolve(&args.given, rom, &config)?;
+    log::info!("Settings for {}: {:?}", rom, found);
+    let palette = found.palette.unwrap_or(args.video.palette.clone());
+    let keymap = found.keymap.unwrap_or(args.keymap.clone());
+    let theme = args.theme()?;
+    let mut builder = args
+        .video
+        .apply(args.memory.apply(emulator::builder()))
+        .memory_faults(args.memory_faults)
+        .rng(args.rng.clone())
+        .palette(palette.clone())
+        .keymap(keymap.clone())
+        .quirks(found.quirks.unwrap_or(quirks))
+        .instructions_per_frame(found.instructions_per_frame.unwrap_or(args.instructions_per_frame))
+        .timing(args.timing)
+        .theme(theme.clone())
+        .screen(args.screen)
+        .screenshot_format(args.screenshot)
+        .persistence(args.persistence)
+        .headless(args.tui)
+        .tone(emulator::audio::Tone {
+            frequency: args.tone,
+            volume: args.volume,
+        });
+    if let Some(seed) = args.seed {
+        builder = builder.seed(seed);
+    }
+    if let Some(wait) = args.display_wait {
+        builder = builder.display_wait(wait);
+    }
+    let mut emul = builder.try_build()?;
+    from_file::read(rom, emul.get_pgrm_mem());
+    match (&args.record_movie, &args.play_movie) {
+        (Some(path), _) => emul.record_movie(path)?,
+        (_, Some(path)) => emul.play_movie(path)?,
+        _ => {}
+    }
+    emul.output_audio(&args.audio)?;
+    if let Some(path) = &args.video.record_video {
+        emul.record_video(path)?;
+    }
+    if args.tui {
+        return tui::run(&mut emul, &theme, &palette, keymap);
+    }
+    return emul.run().map_err(|e| e.to_string());
 }
@@ -0,0 +1,56 @@
+use crate::cli::RenderArgs;
+use log::error;
+use rusty::emulator;
+use rusty::from_file;
+
+pub fn run(args: &RenderArgs) {
+    let mut builder = args
+        .video
+        .apply(args.memory.apply(emulator::builder()))
+        .headless(true)
+        .seed(args.seed)
+        .rng(args.rng.clone())
+        .quirks(args.quirks)
+        .instructions_per_frame(args.instructions_per_frame)
+        .timing(args.timing)
+        .tone(emulator::audio::Tone {
+            frequency: args.tone,
+            volume: args.volume,
+        });
+    if let Some(wait) = args.display_wait {
+        builder = builder.display_wait(wait);
+    }
+    let mut emul = match builder.try_build() {
+        Ok(emul) => emul,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    from_file::read(&args.rom, emul.get_pgrm_mem());
+    if let Some(path) = &args.keys {
+        if let Err(e) = emul.script_keys(path) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = emul.output_audio(&args.audio) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+    if let Some(path) = &args.video.record_video {
+        if let Err(e) = emul.record_video(path) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = emul.run_frames(args.frames) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+    emul.stop_video();
+    if let Err(e) = emul.save_screen(&args.output) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
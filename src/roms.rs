@@ -0,0 +1,60 @@
+use crate::emulator::quirks::{self, Profile};
+use std::path::{Path, PathBuf};
+
+const EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+
+// A ROM found under the launcher's directory
+pub struct Entry {
+    pub path: PathBuf,
+    // Directory relative to the root, e.g. "games"
+    pub category: String,
+    pub name: String,
+    // The .txt file of the same name, as in the chip8-roms collection
+    pub notes: Option<String>,
+    pub profile: Profile,
+}
+
+fn find(root: &Path, dir: &Path, found: &mut Vec<Entry>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find(root, &path, found);
+            continue;
+        }
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        if !EXTENSIONS.contains(&extension.to_lowercase().as_str()) {
+            continue;
+        }
+        let Ok(rom) = std::fs::read(&path) else {
+            continue;
+        };
+        let category = dir.strip_prefix(root).unwrap_or(dir).to_string_lossy();
+        found.push(Entry {
+            category: if category.is_empty() {
+                ".".to_string()
+            } else {
+                category.to_string()
+            },
+            name: path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            notes: std::fs::read_to_string(path.with_extension("txt")).ok(),
+            profile: quirks::detect(&rom, 0x200),
+            path,
+        });
+    }
+}
+
+// Every ROM under the directory, sorted by category and name
+pub fn scan(root: &Path) -> Vec<Entry> {
+    let mut found = Vec::new();
+    find(root, root, &mut found);
+    found.sort_by(|a, b| (&a.category, &a.name).cmp(&(&b.category, &b.name)));
+    return found;
+}
@@ -0,0 +1,494 @@
+use ratatui::crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use ratatui::crossterm::{execute, terminal};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use rusty::emulator::disassembler::disassemble;
+use rusty::emulator::keymap::Keymap;
+use rusty::emulator::orientation::Orientation;
+use rusty::emulator::palette::Palette;
+use rusty::emulator::theme::{self, Theme};
+use rusty::emulator::Emulator;
+use std::time::{Duration, Instant};
+
+const FRAME: Duration = Duration::from_micros(16667);
+// Terminals without key release events only repeat a held key after a delay, so a press holds
+// its keypad key for this many frames
+const KEY_HOLD_FRAMES: u32 = 15;
+const MEMORY_ROW: usize = 16;
+
+// Rows of the register pane: V0-VF, then these
+const REGISTERS: [&str; 5] = ["I", "PC", "SP", "DT", "ST"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Registers,
+    Memory,
+}
+
+struct Tui {
+    // Unlit and lit pixels
+    colors: [Color; 2],
+    turn: Orientation,
+    keymap: Keymap,
+    paused: bool,
+    focus: Focus,
+    register: usize, // Selected row of the register pane
+    cursor: usize,   // Selected address of the memory pane
+    editing: Option<String>,
+    // Frames each keypad key stays held, or with release events u32::MAX until released
+    held: [u32; 16],
+    releases: bool,
+    status: String,
+    quit: bool,
+}
+
+// Runs the emulator in a full screen view of the display, registers, disassembly, call stack
+// and memory. The emulator should be built headless: the view draws the screen and reads keys
+pub fn run(
+    emul: &mut Emulator,
+    theme: &Theme,
+    palette: &Palette,
+    keymap: Keymap,
+) -> Result<(), String> {
+    let mut terminal = ratatui::init();
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if releases {
+        let flags = KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+        let _ = execute!(std::io::stdout(), PushKeyboardEnhancementFlags(flags));
+    }
+    let mut tui = Tui {
+        colors: colors(theme, palette),
+        turn: emul.orientation(),
+        keymap,
+        paused: false,
+        focus: Focus::Registers,
+        register: 0,
+        cursor: emul.state().pc,
+        editing: None,
+        held: [0; 16],
+        releases,
+        status: String::new(),
+        quit: false,
+    };
+    let result = tui.run(&mut terminal, emul);
+    emul.finish();
+    if releases {
+        let _ = execute!(std::io::stdout(), PopKeyboardEnhancementFlags);
+    }
+    ratatui::restore();
+    return result;
+}
+
+fn color(color: theme::Color) -> Color {
+    match color {
+        theme::Color::Default => return Color::Reset,
+        theme::Color::Ansi(n) | theme::Color::Indexed(n) => return Color::Indexed(n),
+        theme::Color::Rgb([r, g, b]) => return Color::Rgb(r, g, b),
+    }
+}
+
+// The theme's colours for unlit and lit pixels. Half blocks can't show glyphs, so themes that only
+// change glyphs, such as emoji, leave the terminal's colours for both and use the palette instead
+fn colors(theme: &Theme, palette: &Palette) -> [Color; 2] {
+    if theme.colors[0] != theme.colors[1] {
+        return [color(theme.colors[0]), color(theme.colors[1])];
+    }
+    return [false, true].map(|lit| {
+        let [r, g, b] = palette.color(lit);
+        return Color::Rgb(r, g, b);
+    });
+}
+
+impl Tui {
+    fn run(&mut self, terminal: &mut DefaultTerminal, emul: &mut Emulator) -> Result<(), String> {
+        let mut deadline = Instant::now();
+        while !self.quit {
+            terminal
+                .draw(|frame| self.draw(frame, emul))
+                .map_err(|e| e.to_string())?;
+            deadline += FRAME;
+            let now = Instant::now();
+            if deadline < now {
+                deadline = now;
+            }
+            while let Ok(true) = event::poll(deadline.saturating_duration_since(Instant::now())) {
+                if let Event::Key(key) = event::read().map_err(|e| e.to_string())? {
+                    self.key(key, emul);
+                }
+            }
+            if !self.paused {
+                self.frame(emul);
+            }
+        }
+        return Ok(());
+    }
+
+    fn keypad(&self) -> u16 {
+        let mut keys = 0;
+        for (key, frames) in self.held.iter().enumerate() {
+            if *frames > 0 {
+                keys |= 1 << key;
+            }
+        }
+        return keys;
+    }
+
+    fn frame(&mut self, emul: &mut Emulator) {
+        emul.hold_keys(self.keypad());
+        let result = emul.run_frame();
+        self.report(result);
+        if !self.releases {
+            for frames in self.held.iter_mut() {
+                *frames = frames.saturating_sub(1);
+            }
+        }
+    }
+
+    fn step(&mut self, emul: &mut Emulator) {
+        emul.hold_keys(self.keypad());
+        let result = emul.step();
+        self.report(result);
+    }
+
+    // Pauses on the end of the program or an error
+    fn report(&mut self, result: Result<bool, rusty::emulator::Error>) {
+        match result {
+            Ok(true) => {}
+            Ok(false) => {
+                self.paused = true;
+                self.status = "The program ended".to_string();
+            }
+            Err(e) => {
+                self.paused = true;
+                self.status = e.to_string();
+            }
+        }
+    }
+
+    fn key(&mut self, key: KeyEvent, emul: &mut Emulator) {
+        if key.kind == KeyEventKind::Release {
+            if let KeyCode::Char(c) = key.code {
+                if let Some(n) = self.keymap.key(c) {
+                    self.held[n] = 0;
+                }
+            }
+            return;
+        }
+        if self.editing.is_some() {
+            self.edit_key(key.code, emul);
+            return;
+        }
+        match key.code {
+            KeyCode::Esc | KeyCode::F(10) => self.quit = true,
+            KeyCode::F(1) => {
+                self.paused = !self.paused;
+                self.status.clear();
+            }
+            KeyCode::F(2) if self.paused => self.frame(emul),
+            KeyCode::F(3) if self.paused => self.step(emul),
+            KeyCode::F(6) => {
+                self.status = match emul.screenshot() {
+                    Ok(path) => format!("Saved {}", path),
+                    Err(e) => e,
+                };
+            }
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Registers => Focus::Memory,
+                    Focus::Memory => Focus::Registers,
+                };
+            }
+            // Editing pauses, so that the value isn't overwritten under the cursor. SP only
+            // changes with the call stack
+            KeyCode::Enter if !(self.focus == Focus::Registers && self.register == 18) => {
+                self.paused = true;
+                self.editing = Some(String::new());
+            }
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right => {
+                self.select(key.code, emul)
+            }
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(16 * MEMORY_ROW),
+            KeyCode::PageDown => {
+                self.cursor = (self.cursor + 16 * MEMORY_ROW).min(emul.addressable() - 1)
+            }
+            KeyCode::Char(c) => {
+                if let Some(n) = self.keymap.key(c) {
+                    self.held[n] = if self.releases {
+                        u32::MAX
+                    } else {
+                        KEY_HOLD_FRAMES
+                    };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn select(&mut self, code: KeyCode, emul: &Emulator) {
+        let rows = 16 + REGISTERS.len();
+        // Ram past the last address wraps onto the start, so it can't be edited
+        let last = emul.addressable() - 1;
+        match (self.focus, code) {
+            (Focus::Registers, KeyCode::Up) => self.register = (self.register + rows - 1) % rows,
+            (Focus::Registers, KeyCode::Down) => self.register = (self.register + 1) % rows,
+            (Focus::Memory, KeyCode::Up) => self.cursor = self.cursor.saturating_sub(MEMORY_ROW),
+            (Focus::Memory, KeyCode::Down) => self.cursor = (self.cursor + MEMORY_ROW).min(last),
+            (Focus::Memory, KeyCode::Left) => self.cursor = self.cursor.saturating_sub(1),
+            (Focus::Memory, KeyCode::Right) => self.cursor = (self.cursor + 1).min(last),
+            _ => {}
+        }
+    }
+
+    // Hex digits the selected field holds: a byte, or an address for I and PC
+    fn width(&self) -> usize {
+        if self.focus == Focus::Registers && (self.register == 16 || self.register == 17) {
+            return 4;
+        }
+        return 2;
+    }
+
+    fn edit_key(&mut self, code: KeyCode, emul: &mut Emulator) {
+        let width = self.width();
+        let Some(text) = &mut self.editing else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.editing = None,
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) if c.is_ascii_hexdigit() && text.len() < width => text.push(c),
+            KeyCode::Enter => {
+                let value = usize::from_str_radix(text, 16).ok();
+                self.editing = None;
+                if let Some(value) = value {
+                    match self.commit(value, emul) {
+                        Ok(()) => self.status.clear(),
+                        Err(e) => self.status = e,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn commit(&mut self, value: usize, emul: &mut Emulator) -> Result<(), String> {
+        if self.focus == Focus::Memory {
+            emul.load(self.cursor, &[value as u8]);
+            return Ok(());
+        }
+        // Bytes can't be longer than their two digits, but an address can be past the last one
+        if self.width() == 4 && value >= emul.addressable() {
+            return Err(format!(
+                "{:x} is past the last address, {:x}",
+                value,
+                emul.addressable() - 1
+            ));
+        }
+        let mut state = emul.state();
+        match self.register {
+            n if n < 16 => state.v[n] = value as u8,
+            16 => state.i = value as u16,
+            17 => state.pc = value,
+            19 => state.delay_timer = value as u8,
+            20 => state.sound_timer = value as u8,
+            _ => {}
+        }
+        emul.set_state(state);
+        return Ok(());
+    }
+
+    fn draw(&self, frame: &mut Frame, emul: &Emulator) {
+        let [top, bottom, help] = Layout::vertical([
+            Constraint::Length(self.turn.size().0 as u16 / 2 + 2),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [screen, registers, calls] = Layout::horizontal([
+            Constraint::Length(self.turn.size().1 as u16 + 2),
+            Constraint::Length(24),
+            Constraint::Min(0),
+        ])
+        .areas(top);
+        let [code, memory] =
+            Layout::horizontal([Constraint::Length(30), Constraint::Min(0)]).areas(bottom);
+        self.draw_screen(frame, screen, emul);
+        self.draw_registers(frame, registers, emul);
+        draw_calls(frame, calls, emul);
+        draw_code(frame, code, emul);
+        self.draw_memory(frame, memory, emul);
+
+        let state = if self.paused { "Paused" } else { "Running" };
+        let text = format!(
+            "{} {} | F1 pause  F2 frame  F3 step  F6 screenshot  Tab pane  Enter edit  Esc quit",
+            state, self.status
+        );
+        frame.render_widget(Paragraph::new(text), help);
+    }
+
+    // Two rows of pixels per line, the top one in the foreground of a half block, turned as the
+    // other renderers are
+    fn draw_screen(&self, frame: &mut Frame, area: Rect, emul: &Emulator) {
+        let pixel = |i: usize, j: usize| {
+            let (h, w) = self.turn.source(i, j);
+            return self.colors[emul.pixel(w, h) as usize];
+        };
+        let (height, width) = self.turn.size();
+        let mut lines = Vec::new();
+        for i in (0..height).step_by(2) {
+            let spans: Vec<Span> = (0..width)
+                .map(|j| {
+                    let style = Style::default().fg(pixel(i, j)).bg(pixel(i + 1, j));
+                    return Span::styled("▀", style);
+                })
+                .collect();
+            lines.push(Line::from(spans));
+        }
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Screen")),
+            area,
+        );
+    }
+
+    fn draw_registers(&self, frame: &mut Frame, area: Rect, emul: &Emulator) {
+        let state = emul.state();
+        let mut rows: Vec<(String, String)> = (0..16)
+            .map(|n| (format!("V{:X}", n), format!("{:02x}", state.v[n])))
+            .collect();
+        rows.push(("I".to_string(), format!("{:04x}", state.i)));
+        rows.push(("PC".to_string(), format!("{:04x}", state.pc)));
+        rows.push(("SP".to_string(), format!("{}", state.calls.len())));
+        rows.push(("DT".to_string(), format!("{:02x}", state.delay_timer)));
+        rows.push(("ST".to_string(), format!("{:02x}", state.sound_timer)));
+        // Two columns: V0-V7 and V8-VF, then I, PC, SP, DT and ST on the left
+        let mut lines = Vec::new();
+        for line in 0..8 {
+            let mut spans = Vec::new();
+            for row in [line, line + 8] {
+                spans.push(self.register_span(row, &rows[row]));
+                spans.push(Span::raw("  "));
+            }
+            lines.push(Line::from(spans));
+        }
+        for (row, register) in rows.iter().enumerate().skip(16) {
+            lines.push(Line::from(self.register_span(row, register)));
+        }
+        let title = if self.focus == Focus::Registers {
+            "Registers *"
+        } else {
+            "Registers"
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn register_span(&self, row: usize, (name, value): &(String, String)) -> Span<'static> {
+        let selected = self.focus == Focus::Registers && self.register == row;
+        let value = match &self.editing {
+            Some(text) if selected => format!("{}_", text),
+            _ => value.clone(),
+        };
+        let style = if selected {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        return Span::styled(format!("{:>2} {:<4}", name, value), style);
+    }
+
+    // Rows of 16 bytes around the cursor. I is highlighted in yellow and PC in cyan, and ram out of
+    // reach of the profile's addresses is greyed out
+    fn draw_memory(&self, frame: &mut Frame, area: Rect, emul: &Emulator) {
+        let memory = emul.memory();
+        let state = emul.state();
+        let rows = (area.height as usize).saturating_sub(2).max(1);
+        let first_row = (self.cursor / MEMORY_ROW).saturating_sub(rows / 2);
+        let last_row = (first_row + rows).min(memory.len() / MEMORY_ROW);
+        let mut lines = Vec::new();
+        for row in first_row..last_row {
+            let mut spans = vec![Span::raw(format!("{:04x} ", row * MEMORY_ROW))];
+            let start = row * MEMORY_ROW;
+            for (address, byte) in memory.iter().enumerate().skip(start).take(MEMORY_ROW) {
+                let mut style = Style::default();
+                if address >= emul.addressable() {
+                    style = style.fg(Color::DarkGray);
+                } else if address == state.i as usize {
+                    style = style.fg(Color::Black).bg(Color::Yellow);
+                } else if address == state.pc || address == state.pc + 1 {
+                    style = style.fg(Color::Cyan);
+                }
+                let mut text = format!("{:02x}", byte);
+                if self.focus == Focus::Memory && address == self.cursor {
+                    style = style.add_modifier(Modifier::REVERSED);
+                    if let Some(editing) = &self.editing {
+                        text = format!("{:_<2}", editing);
+                    }
+                }
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(text, style));
+            }
+            lines.push(Line::from(spans));
+        }
+        let title = if self.focus == Focus::Memory {
+            "Memory *"
+        } else {
+            "Memory"
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+}
+
+// Return addresses, innermost first
+fn draw_calls(frame: &mut Frame, area: Rect, emul: &Emulator) {
+    let lines: Vec<Line> = emul
+        .state()
+        .calls
+        .iter()
+        .rev()
+        .map(|call| Line::from(format!("{:04x}", call)))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Calls")),
+        area,
+    );
+}
+
+// Opcodes before and after PC, at PC's alignment
+fn draw_code(frame: &mut Frame, area: Rect, emul: &Emulator) {
+    let memory = emul.memory();
+    let pc = emul.state().pc;
+    let rows = (area.height as usize).saturating_sub(2);
+    let before = rows / 3;
+    let first = pc.saturating_sub(2 * before);
+    let first = first + (pc - first) % 2;
+    let mut lines = Vec::new();
+    for address in (first..memory.len().saturating_sub(1))
+        .step_by(2)
+        .take(rows)
+    {
+        let inst = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+        let text = format!("{:04x} {:04x} {}", address, inst, disassemble(inst));
+        if address == pc {
+            let style = Style::default().add_modifier(Modifier::REVERSED);
+            lines.push(Line::from(Span::styled(text, style)));
+        } else {
+            lines.push(Line::from(text));
+        }
+    }
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Code")),
+        area,
+    );
+}
@@ -0,0 +1,101 @@
+// The sound timer's tone, as written to a wav file
+use rusty::emulator::quirks::Profile;
+use rusty::emulator::{self, audio};
+
+const START: usize = 0x200;
+
+// Runs the code for the given number of frames and returns the samples written
+fn record(name: &str, code: &[u8], frames: u64, tone: audio::Tone) -> Vec<i16> {
+    return record_with(name, code, frames, tone, Profile::Modern);
+}
+
+fn record_with(
+    name: &str,
+    code: &[u8],
+    frames: u64,
+    tone: audio::Tone,
+    profile: Profile,
+) -> Vec<i16> {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let path = path.to_str().unwrap();
+    let mut emul = emulator::builder()
+        .headless(true)
+        .tone(tone)
+        .quirks(profile)
+        .build();
+    emul.output_audio(&audio::Output::Wav(path.to_string()))
+        .unwrap();
+    emul.load(START, code);
+    emul.run_frames(frames).unwrap();
+    drop(emul);
+
+    let bytes = std::fs::read(path).unwrap();
+    assert_eq!(&bytes[..4], b"RIFF");
+    assert_eq!(&bytes[36..40], b"data");
+    let len = u32::from_le_bytes(bytes[40..44].try_into().unwrap()) as usize;
+    assert_eq!(len, bytes.len() - 44);
+    return bytes[44..]
+        .chunks(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+}
+
+#[test]
+fn tone_lasts_as_long_as_the_sound_timer() {
+    // ST = 30, then loop
+    let samples = record(
+        "timer.wav",
+        &[0x60, 30, 0xF0, 0x18, 0x12, 0x04],
+        60,
+        audio::default_tone(),
+    );
+    let frame = audio::SAMPLES_PER_FRAME as usize;
+    assert_eq!(samples.len(), 60 * frame);
+    let sounding = samples.iter().take_while(|sample| **sample != 0).count();
+    assert_eq!(sounding, 30 * frame);
+    assert!(samples[sounding..].iter().all(|sample| *sample == 0));
+}
+
+#[test]
+fn frequency_and_volume() {
+    let tone = audio::Tone {
+        frequency: 1000.0,
+        volume: 0.5,
+    };
+    let samples = record("tone.wav", &[0x60, 60, 0xF0, 0x18, 0x12, 0x04], 61, tone);
+    let second = &samples[..audio::SAMPLE_RATE as usize];
+    // One rising edge per period
+    let edges = second
+        .windows(2)
+        .filter(|pair| pair[0] < 0 && pair[1] > 0)
+        .count();
+    assert!((999..=1001).contains(&edges), "{} edges", edges);
+    assert_eq!(second.iter().max(), Some(&(i16::MAX / 2)));
+}
+
+#[test]
+fn xo_chip_pattern_and_pitch() {
+    let code = [
+        0xA3, 0x00, // I = 0x300
+        0xF0, 0x02, // Load the pattern
+        0x60, 112, // Pitch 112: 8000 samples per second
+        0xF0, 0x3A, //
+        0x60, 60, // ST = 60
+        0xF0, 0x18, //
+        0x12, 0x0C, // Loop
+    ];
+    let mut program = code.to_vec();
+    program.resize(0x100, 0);
+    // 4 samples high then 4 low, so 16 cycles per loop of the pattern
+    program.extend([0xF0; 16]);
+    let tone = audio::default_tone();
+    let samples = record_with("pattern.wav", &program, 61, tone, Profile::XoChip);
+    let second = &samples[..audio::SAMPLE_RATE as usize];
+    let edges = second
+        .windows(2)
+        .filter(|pair| pair[0] < 0 && pair[1] > 0)
+        .count();
+    assert!((999..=1001).contains(&edges), "{} edges", edges);
+    assert_eq!(audio::pattern_rate(64), 4000.0);
+    assert_eq!(audio::pattern_rate(112), 8000.0);
+}
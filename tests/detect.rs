@@ -0,0 +1,25 @@
+// Picking a quirk profile from the opcodes a ROM can reach, for the launcher
+use rusty::emulator::quirks::{detect, Profile};
+
+#[test]
+fn platforms() {
+    // CLS, LD V0, DRW, JP back to the draw
+    let plain = [0x00, 0xE0, 0x60, 0x05, 0xD0, 0x05, 0x12, 0x04];
+    assert_eq!(detect(&plain, 0x200), Profile::Vip);
+
+    // Hires mode after a skip, then exit
+    let schip = [0x30, 0x01, 0x00, 0xFF, 0x00, 0xFD];
+    assert_eq!(detect(&schip, 0x200), Profile::Schip);
+
+    // A call into a routine that loads an audio pattern
+    let xo = [0x22, 0x04, 0x12, 0x02, 0xF0, 0x02, 0x00, 0xEE];
+    assert_eq!(detect(&xo, 0x200), Profile::XoChip);
+
+    // Sprite data after an endless loop is never reached, even if it reads as 00FF
+    let data = [0x12, 0x00, 0x00, 0xFF, 0xF0, 0x02];
+    assert_eq!(detect(&data, 0x200), Profile::Vip);
+
+    // Loaded somewhere else
+    assert_eq!(detect(&schip, 0x600), Profile::Schip);
+    assert_eq!(detect(&[], 0x200), Profile::Vip);
+}
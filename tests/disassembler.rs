@@ -0,0 +1,31 @@
+// Mnemonics for the TUI's code pane
+use rusty::emulator::disassembler::disassemble;
+
+#[test]
+fn mnemonics() {
+    for (inst, text) in [
+        (0x00E0, "CLS"),
+        (0x00EE, "RET"),
+        (0x1234, "JP 0x234"),
+        (0x2ABC, "CALL 0xabc"),
+        (0x3A07, "SE VA, 0x07"),
+        (0x5120, "SE V1, V2"),
+        (0x6F10, "LD VF, 0x10"),
+        (0x8126, "SHR V1, V2"),
+        (0x812E, "SHL V1, V2"),
+        (0xA050, "LD I, 0x050"),
+        (0xB200, "JP V0, 0x200"),
+        (0xD015, "DRW V0, V1, 5"),
+        (0xE39E, "SKP V3"),
+        (0xF00A, "LD V0, K"),
+        (0xF233, "LD B, V2"),
+        (0xF565, "LD V5, [I]"),
+        (0xF002, "AUDIO"),
+        (0xF13A, "PITCH V1"),
+    ] {
+        assert_eq!(disassemble(inst), text, "{:04x}", inst);
+    }
+    for data in [0x8128, 0xE100, 0xF1FF, 0xFFFF] {
+        assert_eq!(disassemble(data), format!("DW {:#06x}", data));
+    }
+}
@@ -0,0 +1,126 @@
+// Runs ROMs headless and compares the final screen against tests/golden/screens.txt:
+//   - every ROM of the in-repo set in tests/golden/roms, named roms/<file>
+//   - the chip8-roms submodule ROMs listed in tests/golden/pinned.txt, skipping those missing
+// A ROM without an entry fails the test; run with RUSTY_UPDATE_GOLDEN=1 to add it.
+//
+// Inputs come from tests/golden/keys/<rom name>.keys when present, in keyboard::load_script format.
+// On a mismatch the actual screen is written to target/golden/<rom name>.txt.
+use rusty::emulator;
+use rusty::from_file;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const FRAMES: u64 = 300;
+const SEED: u64 = 0;
+const UPDATE_VAR: &str = "RUSTY_UPDATE_GOLDEN";
+
+fn root() -> PathBuf {
+    return PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+}
+
+fn run_rom(rom: &Path, name: &str) -> (String, emulator::Emulator) {
+    let mut emul = emulator::builder().headless(true).seed(SEED).build();
+    from_file::read(rom.to_str().unwrap(), emul.get_pgrm_mem());
+    let keys = root().join("tests/golden/keys").join(format!("{}.keys", name));
+    if keys.exists() {
+        emul.script_keys(keys.to_str().unwrap()).unwrap();
+    }
+    let result = match emul.run_frames(FRAMES) {
+        Ok(()) => format!("{:016x}", emul.screen_hash()),
+        Err(e) => format!("error {}", e),
+    };
+    return (result, emul);
+}
+
+// Lines of a golden file that aren't comments. ROM names have spaces, so fields are split by tabs
+fn read_lines(path: &Path) -> Vec<(String, String)> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let (name, rest) = line.split_once('\t').unwrap_or((line, ""));
+        lines.push((name.to_string(), rest.to_string()));
+    }
+    return lines;
+}
+
+fn save_golden(path: &Path, golden: &BTreeMap<String, String>) {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let mut out: String = text
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    for (name, result) in golden.iter() {
+        out.push_str(&format!("{}\t{}\n", name, result));
+    }
+    std::fs::write(path, out).unwrap();
+}
+
+// (name, path) of every ROM to check
+fn roms() -> Vec<(String, PathBuf)> {
+    let mut roms = Vec::new();
+    let local = root().join("tests/golden/roms");
+    for entry in std::fs::read_dir(&local).unwrap().flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("ch8") {
+            let file = path.file_name().unwrap().to_string_lossy().to_string();
+            roms.push((format!("roms/{}", file), path));
+        }
+    }
+    // An uninitialised submodule is an empty directory, so each ROM is looked for on its own
+    let submodule = root().join("chip8-roms");
+    for (name, _) in read_lines(&root().join("tests/golden/pinned.txt")) {
+        let path = submodule.join(&name);
+        if path.is_file() {
+            roms.push((name, path));
+        } else {
+            println!("{} isn't checked out, skipping it", path.display());
+        }
+    }
+    roms.sort();
+    return roms;
+}
+
+#[test]
+fn golden_screens() {
+    let golden_path = root().join("tests/golden/screens.txt");
+    let mut golden: BTreeMap<String, String> = read_lines(&golden_path).into_iter().collect();
+    let update = std::env::var(UPDATE_VAR).is_ok();
+    let roms = roms();
+    assert!(!roms.is_empty(), "No golden ROMs");
+    let mut failures = Vec::new();
+    for (name, rom) in roms.iter() {
+        let (result, emul) = run_rom(rom, name);
+        if update {
+            golden.insert(name.clone(), result);
+            continue;
+        }
+        match golden.get(name) {
+            None => failures.push(format!(
+                "{}: no golden entry, run with {}=1 to add it",
+                name, UPDATE_VAR
+            )),
+            Some(expected) if *expected == result => {}
+            Some(expected) => {
+                let actual = root().join("target/golden").join(format!("{}.txt", name));
+                std::fs::create_dir_all(actual.parent().unwrap()).unwrap();
+                emul.save_screen(actual.to_str().unwrap()).unwrap();
+                failures.push(format!(
+                    "{}: expected {}, got {} (screen in {})",
+                    name,
+                    expected,
+                    result,
+                    actual.display()
+                ));
+            }
+        }
+    }
+    if update {
+        save_golden(&golden_path, &golden);
+        return;
+    }
+    assert!(failures.is_empty(), "Screens differ:\n{}", failures.join("\n"));
+}
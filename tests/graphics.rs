@@ -0,0 +1,111 @@
+// Sixel and kitty escape sequences, compared against tests/golden/graphics/<name>.txt with ESC
+// written as \e. Regenerate with RUSTY_UPDATE_GOLDEN=1 cargo test --test graphics
+use rusty::emulator::graphics::{self, Mode, Protocol};
+use rusty::emulator::orientation::Orientation;
+use rusty::emulator::{self, Emulator};
+use std::path::PathBuf;
+
+const UPDATE_VAR: &str = "RUSTY_UPDATE_GOLDEN";
+
+// The 0 and 1 glyphs side by side
+fn screen(scale: usize, orientation: Orientation) -> Emulator {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .scale(scale)
+        .orientation(orientation)
+        .palette("102030,f0e0d0".parse().unwrap())
+        .build();
+    let code = [
+        0xA0, 0x50, 0xD0, 0x05, 0x61, 0x01, 0xF1, 0x29, 0x60, 0x08, 0xD0, 0x25,
+    ];
+    emul.load(0x200, &code);
+    for _ in 0..6 {
+        emul.step().unwrap();
+    }
+    return emul;
+}
+
+fn check(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden/graphics")
+        .join(format!("{}.txt", name));
+    let actual = actual.replace('\x1B', "\\e");
+    if std::env::var_os(UPDATE_VAR).is_some() {
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert!(actual == expected, "{} differs from {:?}", name, path);
+}
+
+#[test]
+fn sixel() {
+    let emul = screen(1, Orientation::default());
+    let image = emul.encode_screen(Protocol::Sixel);
+    assert!(image.starts_with("\x1BPq\"1;1;64;32#0;2;6;13;19#1;2;94;88;82"));
+    assert!(image.ends_with("-\x1B\\"));
+    check("sixel", &image);
+
+    let turned = Orientation {
+        rotation: 90,
+        ..Default::default()
+    };
+    check(
+        "sixel-scaled-rotated",
+        &screen(2, turned).encode_screen(Protocol::Sixel),
+    );
+}
+
+#[test]
+fn kitty() {
+    let emul = screen(1, Orientation::default());
+    let image = emul.encode_screen(Protocol::Kitty);
+    assert!(image.starts_with("\x1B_Ga=T,f=24,s=64,v=32,i=1,C=1,q=2,m=1;8ODQ"));
+    check("kitty", &image);
+
+    // 256x128 rgb24 is 131072 bytes of base64, in 32 chunks of 4096
+    let image = screen(4, Orientation::default()).encode_screen(Protocol::Kitty);
+    assert_eq!(image.matches("\x1B_G").count(), 32);
+    assert_eq!(image.matches("m=1;").count(), 31);
+    assert!(image.contains("\x1B_Gm=0;"));
+}
+
+#[test]
+fn only_auto_falls_back_to_text() {
+    for detected in [None, Some(Protocol::Sixel), Some(Protocol::Kitty)] {
+        assert_eq!(graphics::choose_from(Mode::Auto, detected), detected);
+        assert_eq!(graphics::choose_from(Mode::Text, detected), None);
+        for protocol in [Protocol::Sixel, Protocol::Kitty] {
+            let chosen = graphics::choose_from(Mode::Only(protocol), detected);
+            assert_eq!(chosen, Some(protocol));
+        }
+    }
+}
+
+// Looks variables up in a list instead of the environment
+fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    return move |name: &str| {
+        let found = vars.iter().find(|(var, _)| *var == name);
+        return found.map(|(_, value)| value.to_string());
+    };
+}
+
+#[test]
+fn detection() {
+    let detect = |vars: &[(&str, &str)]| graphics::detect_in(true, env(vars));
+    assert_eq!(detect(&[("TERM", "xterm-kitty")]), Some(Protocol::Kitty));
+    assert_eq!(
+        detect(&[("TERM", "xterm-256color"), ("KITTY_WINDOW_ID", "1")]),
+        Some(Protocol::Kitty)
+    );
+    assert_eq!(detect(&[("TERM_PROGRAM", "WezTerm")]), Some(Protocol::Kitty));
+    assert_eq!(detect(&[("TERM", "foot")]), Some(Protocol::Sixel));
+    assert_eq!(
+        detect(&[("TERM", "xterm"), ("TERM_PROGRAM", "mintty")]),
+        Some(Protocol::Sixel)
+    );
+    assert_eq!(detect(&[("TERM", "xterm-256color")]), None);
+    assert_eq!(detect(&[]), None);
+    // Nothing is drawn as pixels into a pipe or a file
+    assert_eq!(graphics::detect_in(false, env(&[("TERM", "xterm-kitty")])), None);
+}
@@ -0,0 +1,341 @@
+// Accesses near the top of ram either wrap around or fault, but never panic
+use rusty::emulator::quirks::Profile;
+use rusty::emulator::{self, font, rng, Emulator, Error};
+
+const PROFILES: [Profile; 4] = [Profile::Modern, Profile::Vip, Profile::Schip, Profile::XoChip];
+
+fn machine(memory_faults: bool) -> Emulator {
+    return emulator::builder()
+        .headless(true)
+        .seed(0)
+        .memory_faults(memory_faults)
+        .build();
+}
+
+// Loads the code at the given address and jumps there with I set
+fn setup(emul: &mut Emulator, pc: usize, i: u16, code: &[u8]) {
+    emul.load(pc, code);
+    let mut state = emul.state();
+    state.pc = pc;
+    state.i = i;
+    state.v[1] = 0x0A;
+    emul.set_state(state);
+}
+
+#[test]
+fn sprite_reads_wrap() {
+    let mut emul = machine(false);
+    let top = emul.memory().len();
+    emul.load(top - 1, &[0xFF, 0x81]);
+    setup(&mut emul, 0x200, (top - 1) as u16, &[0xD0, 0x02]);
+    emul.step().unwrap();
+    assert!(emul.pixel(0, 0) && emul.pixel(7, 0));
+    assert!(emul.pixel(0, 1) && !emul.pixel(1, 1) && emul.pixel(7, 1));
+}
+
+#[test]
+fn stores_wrap() {
+    let mut emul = machine(false);
+    let top = emul.memory().len();
+    setup(&mut emul, 0x200, (top - 1) as u16, &[0xF1, 0x55]);
+    let mut state = emul.state();
+    state.v[0] = 0x12;
+    emul.set_state(state);
+    emul.step().unwrap();
+    assert_eq!(emul.memory()[top - 1], 0x12);
+    assert_eq!(emul.memory()[0], 0x0A);
+
+    setup(&mut emul, 0x200, (top - 2) as u16, &[0xF1, 0x33]);
+    emul.step().unwrap();
+    assert_eq!(emul.memory()[top - 2..], [0, 1]);
+    assert_eq!(emul.memory()[0], 0);
+}
+
+#[test]
+fn accesses_past_the_end_fault() {
+    let mut emul = machine(true);
+    let top = emul.memory().len();
+    for code in [[0xD0, 0x02], [0xF1, 0x55], [0xF1, 0x65], [0xF1, 0x33]] {
+        setup(&mut emul, 0x200, (top - 1) as u16, &code);
+        match emul.step() {
+            Err(Error::MemoryFault { address, target }) => {
+                assert_eq!(address, 0x200);
+                assert_eq!(target, top);
+            }
+            other => panic!("{:02x?}: {:?}", code, other),
+        }
+    }
+}
+
+#[test]
+fn running_off_the_end() {
+    let mut emul = machine(false);
+    let top = emul.memory().len();
+    emul.load(0, &[0x61, 0x05]);
+    setup(&mut emul, top - 2, 0, &[0x61, 0x04]);
+    emul.step().unwrap();
+    emul.step().unwrap();
+    assert_eq!(emul.state().pc, 2);
+    assert_eq!(emul.state().v[1], 5);
+
+    let mut emul = machine(true);
+    setup(&mut emul, top - 2, 0, &[0x61, 0x04]);
+    emul.step().unwrap();
+    assert!(matches!(emul.step(), Err(Error::MemoryFault { .. })));
+}
+
+#[test]
+fn unbounded_recursion_overflows_the_stack() {
+    let mut emul = machine(false);
+    setup(&mut emul, 0x200, 0, &[0x22, 0x00]);
+    for _ in 0..16 {
+        emul.step().unwrap();
+    }
+    assert!(matches!(
+        emul.step(),
+        Err(Error::StackOverflow { address: 0x200 })
+    ));
+}
+
+#[test]
+fn ram_sizes() {
+    for (size, expected) in [(0x1000, 0x1000), (0x10000, 0x10000), (0x3000, 0x4000), (0, 0x1000)] {
+        let emul = emulator::builder().headless(true).ram_size(size).build();
+        assert_eq!(emul.memory().len(), expected);
+    }
+    let mut emul = emulator::builder().headless(true).build();
+    assert_eq!(emul.memory().len(), 0x2000);
+    assert_eq!(emul.get_pgrm_mem().len(), 0x2000 - 0x200);
+}
+
+#[test]
+fn sixteen_bit_addresses_with_64k() {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .ram_size(0x10000)
+        .build();
+    setup(&mut emul, 0x200, 0xFFFF, &[0xF1, 0x55]);
+    emul.step().unwrap();
+    assert_eq!(emul.memory()[0xFFFF], 0);
+    assert_eq!(emul.memory()[0], 0x0A);
+}
+
+#[test]
+fn twelve_bit_addresses() {
+    for profile in [Profile::Vip, Profile::Schip] {
+        let mut emul = emulator::builder()
+            .headless(true)
+            .quirks(profile)
+            .build();
+        assert_eq!(emul.memory().len(), 0x2000);
+        assert_eq!(emul.addressable(), 0x1000, "{}", profile);
+        // I wraps at 0xFFF even though there is ram above it
+        setup(&mut emul, 0x200, 0xFFE, &[0xF3, 0x1E]);
+        let mut state = emul.state();
+        state.v[3] = 3;
+        emul.set_state(state);
+        emul.step().unwrap();
+        assert_eq!(emul.state().i, 0x001, "{}", profile);
+
+        setup(&mut emul, 0x200, 0xFFF, &[0xF1, 0x55]);
+        emul.step().unwrap();
+        assert_eq!(emul.memory()[0x1000], 0, "{}", profile);
+        assert_eq!(emul.memory()[0], 0x0A, "{}", profile);
+
+        // And so does the program counter
+        emul.load(0, &[0x61, 0x05]);
+        setup(&mut emul, 0xFFE, 0, &[0x61, 0x04]);
+        emul.step().unwrap();
+        emul.step().unwrap();
+        assert_eq!(emul.state().pc, 2, "{}", profile);
+        assert_eq!(emul.state().v[1], 5, "{}", profile);
+    }
+    let mut emul = emulator::builder()
+        .headless(true)
+        .quirks(Profile::Vip)
+        .memory_faults(true)
+        .build();
+    setup(&mut emul, 0x200, 0xFFF, &[0xF1, 0x55]);
+    assert!(matches!(
+        emul.step(),
+        Err(Error::MemoryFault {
+            address: 0x200,
+            target: 0x1000
+        })
+    ));
+}
+
+#[test]
+fn sixteen_bit_addresses_on_xo_chip() {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .quirks(Profile::XoChip)
+        .ram_size(0x10000)
+        .build();
+    assert_eq!(emul.addressable(), 0x10000);
+    setup(&mut emul, 0x200, 0xFFE, &[0xF3, 0x1E]);
+    let mut state = emul.state();
+    state.v[3] = 3;
+    emul.set_state(state);
+    emul.step().unwrap();
+    assert_eq!(emul.state().i, 0x1001);
+
+    setup(&mut emul, 0x200, 0xFFFE, &[0xF3, 0x1E, 0xF1, 0x55]);
+    let mut state = emul.state();
+    (state.v[0], state.v[3]) = (0x12, 3);
+    emul.set_state(state);
+    emul.step().unwrap();
+    assert_eq!(emul.state().i, 0x0001);
+    emul.step().unwrap();
+    assert_eq!(emul.memory()[1..3], [0x12, 0x0A]);
+
+    // With less ram addresses still wrap at its end
+    let mut emul = emulator::builder()
+        .headless(true)
+        .quirks(Profile::XoChip)
+        .build();
+    setup(&mut emul, 0x200, 0x1FFF, &[0xF1, 0x55]);
+    emul.step().unwrap();
+    assert_eq!(emul.memory()[0x1FFF], 0);
+    assert_eq!(emul.memory()[0], 0x0A);
+}
+
+#[test]
+fn load_address() {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .load_address(0x600)
+        .build();
+    emul.get_pgrm_mem()[..2].copy_from_slice(&[0x61, 0x07]);
+    assert_eq!(emul.state().pc, 0x600);
+    emul.step().unwrap();
+    assert_eq!(emul.state().v[1], 7);
+    assert_eq!(emul.memory()[0x600], 0x61);
+}
+
+#[test]
+fn font_address() {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .font_address(0x000)
+        .build();
+    assert_eq!(emul.memory()[..5], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    setup(&mut emul, 0x200, 0, &[0xF1, 0x29]);
+    emul.step().unwrap();
+    assert_eq!(emul.state().i, 50);
+}
+
+#[test]
+fn layouts_are_checked() {
+    let error = |builder: emulator::Builder| builder.headless(true).try_build().err();
+    assert_eq!(error(emulator::builder().load_address(0x600).font_address(0x510)), None);
+
+    // The 240 bytes of the font run into the program
+    let overlap = error(emulator::builder().font_address(0x1C0)).unwrap();
+    assert!(overlap.contains("overlaps the program loaded at 0x200"), "{}", overlap);
+    // A font above the program would be overwritten by a long enough ROM
+    assert!(error(emulator::builder().font_address(0x1F00)).is_some());
+    assert!(error(emulator::builder().ram_size(0x1000).font_address(0xFF0)).is_some());
+
+    // The program has to start at an address the platform can reach
+    assert!(error(emulator::builder().load_address(0x2000)).is_some());
+    assert!(error(emulator::builder().quirks(Profile::Vip).load_address(0x1200)).is_some());
+    assert_eq!(error(emulator::builder().quirks(Profile::XoChip).load_address(0x1200)), None);
+}
+
+#[test]
+fn fonts() {
+    let vip = font::named("vip").unwrap();
+    assert_eq!(vip.small.len(), 80);
+    assert_eq!(vip.big.len(), 160);
+    let mut emul = emulator::builder()
+        .headless(true)
+        .font(vip.clone())
+        .build();
+    assert_eq!(emul.memory()[0x50 + 5..0x50 + 10], [0x60, 0x20, 0x20, 0x20, 0x70]);
+    assert_eq!(emul.memory()[0x50 + 80..0x50 + 240], vip.big[..]);
+    setup(&mut emul, 0x200, 0, &[0xF1, 0x30]);
+    emul.step().unwrap();
+    assert_eq!(emul.state().i, 0x50 + 80 + 100);
+
+    // SUPER-CHIP has no big letters
+    assert!(font::named("schip").unwrap().big[100..].iter().all(|byte| *byte == 0));
+    assert!(font::named("amiga").is_none());
+}
+
+#[test]
+fn font_files() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let path = dir.join("font.bin");
+    let mut bytes: Vec<u8> = (0..80).collect();
+    std::fs::write(&path, &bytes).unwrap();
+    let font = font::find(path.to_str().unwrap()).unwrap();
+    assert_eq!(font.small, bytes);
+    assert_eq!(font.big, font::default().big);
+
+    bytes.extend([0xAA; 100]);
+    std::fs::write(&path, &bytes).unwrap();
+    let font = font::load(path.to_str().unwrap()).unwrap();
+    assert_eq!(font.big[..100], bytes[80..]);
+    assert_eq!(font.big[100..], [0; 60]);
+
+    std::fs::write(&path, [0; 81]).unwrap();
+    assert!(font::load(path.to_str().unwrap()).is_err());
+}
+
+// A cheap stand-in for the fuzz target in fuzz/, so that cargo test covers the same ground
+#[test]
+fn random_programs_never_panic() {
+    let mut bytes = rng::create(rng::Mode::Xorshift, 0);
+    for _ in 0..50 {
+        let rom: Vec<u8> = (0..512).map(|_| bytes.next_byte()).collect();
+        for profile in PROFILES {
+            for memory_faults in [false, true] {
+                let mut emul = emulator::builder()
+                    .headless(true)
+                    .seed(0)
+                    .quirks(profile)
+                    .memory_faults(memory_faults)
+                    .build();
+                emul.load(0x200, &rom);
+                for _ in 0..1000 {
+                    match emul.step() {
+                        Ok(true) => {}
+                        _ => break,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn movies_keep_the_font_and_memory_faults() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("font.movie");
+    let path = path.to_str().unwrap();
+    // LD [I], V1 with I at the last byte of ram
+    let code = [0xF1, 0x55];
+    let vip = || font::named("vip").unwrap();
+    let mut emul = emulator::builder()
+        .headless(true)
+        .font(vip())
+        .memory_faults(true)
+        .build();
+    emul.load(0x200, &code);
+    emul.record_movie(path).unwrap();
+
+    // Another font draws other glyphs, so the movie can't be played with it
+    let mut emul = machine(false);
+    emul.load(0x200, &code);
+    let error = emul.play_movie(path).err().unwrap();
+    assert!(error.contains("different font"), "{}", error);
+
+    // But memory faults follow the movie
+    let mut emul = emulator::builder().headless(true).font(vip()).build();
+    emul.load(0x200, &code);
+    emul.play_movie(path).unwrap();
+    let mut state = emul.state();
+    state.i = (emul.memory().len() - 1) as u16;
+    emul.set_state(state);
+    assert!(matches!(emul.step(), Err(Error::MemoryFault { .. })));
+}
@@ -0,0 +1,627 @@
+// One or more tests per opcode, run against every quirk profile where the result depends on it
+use rusty::emulator::quirks::Profile;
+use rusty::emulator::{self, rng, timing, Emulator, Error, State};
+
+const START: usize = 0x200;
+const FONT_POS: u16 = 0x50;
+const PROFILES: [Profile; 4] = [Profile::Modern, Profile::Vip, Profile::Schip, Profile::XoChip];
+
+fn machine(profile: Profile) -> Emulator {
+    return emulator::builder()
+        .headless(true)
+        .rng(rng::Mode::Fixed(vec![0xA5]))
+        .quirks(profile)
+        .build();
+}
+
+// Loads the code at 0x200, applies the setup and executes one instruction per opcode given
+fn run(profile: Profile, code: &[u16], setup: impl FnOnce(&mut State)) -> Emulator {
+    let mut emul = machine(profile);
+    let bytes: Vec<u8> = code.iter().flat_map(|inst| inst.to_be_bytes()).collect();
+    emul.load(START, &bytes);
+    let mut state = emul.state();
+    setup(&mut state);
+    emul.set_state(state);
+    for _ in 0..code.len() {
+        emul.step().unwrap();
+    }
+    return emul;
+}
+
+fn run_modern(code: &[u16], setup: impl FnOnce(&mut State)) -> State {
+    return run(Profile::Modern, code, setup).state();
+}
+
+#[test]
+fn clear_screen() {
+    let emul = run(Profile::Modern, &[0xA050, 0xD005, 0x00E0], |_| {});
+    for x in 0..64 {
+        for y in 0..32 {
+            assert!(!emul.pixel(x, y));
+        }
+    }
+}
+
+#[test]
+fn call_and_return() {
+    let state = run_modern(&[0x2204], |_| {});
+    assert_eq!(state.pc, 0x204);
+    assert_eq!(state.calls, vec![0x202]);
+
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &[0x22, 0x04, 0x00, 0x00, 0x00, 0xEE]);
+    emul.step().unwrap();
+    assert!(emul.step().unwrap());
+    assert_eq!(emul.state().pc, 0x202);
+    assert!(emul.state().calls.is_empty());
+}
+
+#[test]
+fn nested_calls_return_in_order() {
+    // 200 calls 206, which calls 20A, which returns to 208 and then to 202
+    let mut emul = machine(Profile::Modern);
+    emul.load(
+        START,
+        &[0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x22, 0x0A, 0x00, 0xEE, 0x00, 0xEE],
+    );
+    emul.step().unwrap();
+    emul.step().unwrap();
+    assert_eq!(emul.state().calls, vec![0x202, 0x208]);
+    emul.step().unwrap();
+    assert_eq!(emul.state().pc, 0x208);
+    emul.step().unwrap();
+    assert_eq!(emul.state().pc, 0x202);
+    assert!(emul.state().calls.is_empty());
+}
+
+#[test]
+fn deepest_recursion_unwinds() {
+    // 200: SE V0, 10 / 202: JP 208 / 204: 00EE / 208: ADD V0, 1 / 20A: CALL 200 / 20C: 00EE
+    let mut emul = machine(Profile::Modern);
+    emul.load(
+        START,
+        &[
+            0x30, 0x10, 0x12, 0x08, 0x00, 0xEE, 0x00, 0x00, 0x70, 0x01, 0x22, 0x00, 0x00, 0xEE,
+        ],
+    );
+    let mut deepest = 0;
+    while emul.step().unwrap() {
+        deepest = deepest.max(emul.state().calls.len());
+    }
+    // Returning from the top level ends the program once every call has come back
+    assert_eq!(deepest, 16);
+    assert_eq!(emul.state().v[0], 0x10);
+    assert!(emul.state().calls.is_empty());
+}
+
+#[test]
+fn return_from_top_level_ends_program() {
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &[0x00, 0xEE]);
+    assert!(!emul.step().unwrap());
+}
+
+#[test]
+fn jump() {
+    assert_eq!(run_modern(&[0x1ABC], |_| {}).pc, 0xABC);
+}
+
+#[test]
+fn skip_if_equal_immediate() {
+    assert_eq!(run_modern(&[0x3312], |s| s.v[3] = 0x12).pc, 0x204);
+    assert_eq!(run_modern(&[0x3312], |s| s.v[3] = 0x13).pc, 0x202);
+}
+
+#[test]
+fn skip_if_not_equal_immediate() {
+    assert_eq!(run_modern(&[0x4312], |s| s.v[3] = 0x12).pc, 0x202);
+    assert_eq!(run_modern(&[0x4312], |s| s.v[3] = 0x13).pc, 0x204);
+}
+
+#[test]
+fn skip_if_registers_equal() {
+    assert_eq!(run_modern(&[0x5340], |s| (s.v[3], s.v[4]) = (7, 7)).pc, 0x204);
+    assert_eq!(run_modern(&[0x5340], |s| (s.v[3], s.v[4]) = (7, 8)).pc, 0x202);
+}
+
+#[test]
+fn skip_if_registers_differ() {
+    assert_eq!(run_modern(&[0x9340], |s| (s.v[3], s.v[4]) = (7, 7)).pc, 0x202);
+    assert_eq!(run_modern(&[0x9340], |s| (s.v[3], s.v[4]) = (7, 8)).pc, 0x204);
+}
+
+#[test]
+fn set_register() {
+    assert_eq!(run_modern(&[0x6A42], |_| {}).v[0xA], 0x42);
+}
+
+#[test]
+fn add_immediate_wraps_without_carry() {
+    let state = run_modern(&[0x7A02], |s| (s.v[0xA], s.v[0xF]) = (0xFF, 7));
+    assert_eq!(state.v[0xA], 0x01);
+    assert_eq!(state.v[0xF], 7);
+}
+
+#[test]
+fn copy_register() {
+    assert_eq!(run_modern(&[0x8120], |s| s.v[2] = 9).v[1], 9);
+}
+
+#[test]
+fn bitwise_ops_and_vf_reset() {
+    for profile in PROFILES {
+        let vf = if profile.quirks().vf_reset { 0 } else { 5 };
+        for (inst, expected) in [(0x8121, 0b1110), (0x8122, 0b1000), (0x8123, 0b0110)] {
+            let state = run(profile, &[inst], |s| {
+                s.v[1] = 0b1100;
+                s.v[2] = 0b1010;
+                s.v[0xF] = 5;
+            })
+            .state();
+            assert_eq!(state.v[1], expected, "{:#06x} {}", inst, profile);
+            assert_eq!(state.v[0xF], vf, "{:#06x} {}", inst, profile);
+        }
+    }
+}
+
+#[test]
+fn add_registers_sets_carry() {
+    let state = run_modern(&[0x8124], |s| (s.v[1], s.v[2]) = (0xF0, 0x20));
+    assert_eq!((state.v[1], state.v[0xF]), (0x10, 1));
+    let state = run_modern(&[0x8124], |s| (s.v[1], s.v[2]) = (0x10, 0x20));
+    assert_eq!((state.v[1], state.v[0xF]), (0x30, 0));
+}
+
+#[test]
+fn subtract_sets_not_borrow() {
+    let state = run_modern(&[0x8125], |s| (s.v[1], s.v[2]) = (5, 3));
+    assert_eq!((state.v[1], state.v[0xF]), (2, 1));
+    let state = run_modern(&[0x8125], |s| (s.v[1], s.v[2]) = (3, 5));
+    assert_eq!((state.v[1], state.v[0xF]), (0xFE, 0));
+    // No borrow when the operands are equal
+    let state = run_modern(&[0x8125], |s| (s.v[1], s.v[2]) = (4, 4));
+    assert_eq!((state.v[1], state.v[0xF]), (0, 1));
+}
+
+#[test]
+fn reverse_subtract_sets_not_borrow() {
+    let state = run_modern(&[0x8127], |s| (s.v[1], s.v[2]) = (3, 5));
+    assert_eq!((state.v[1], state.v[0xF]), (2, 1));
+    let state = run_modern(&[0x8127], |s| (s.v[1], s.v[2]) = (5, 3));
+    assert_eq!((state.v[1], state.v[0xF]), (0xFE, 0));
+    let state = run_modern(&[0x8127], |s| (s.v[1], s.v[2]) = (4, 4));
+    assert_eq!((state.v[1], state.v[0xF]), (0, 1));
+}
+
+#[test]
+fn flag_wins_when_vf_is_the_destination() {
+    assert_eq!(run_modern(&[0x8F14], |s| (s.v[1], s.v[0xF]) = (0xFF, 0x02)).v[0xF], 1);
+    assert_eq!(run_modern(&[0x8F15], |s| (s.v[1], s.v[0xF]) = (0x01, 0x00)).v[0xF], 0);
+    assert_eq!(run_modern(&[0x8F06], |s| s.v[0xF] = 0x03).v[0xF], 1);
+}
+
+#[test]
+fn shift_right() {
+    for profile in PROFILES {
+        let state = run(profile, &[0x8126], |s| (s.v[1], s.v[2]) = (0b0110, 0b0011)).state();
+        if profile.quirks().shift_uses_vy {
+            assert_eq!((state.v[1], state.v[0xF]), (0b0001, 1), "{}", profile);
+        } else {
+            assert_eq!((state.v[1], state.v[0xF]), (0b0011, 0), "{}", profile);
+        }
+    }
+}
+
+#[test]
+fn shift_left() {
+    for profile in PROFILES {
+        let state = run(profile, &[0x812E], |s| (s.v[1], s.v[2]) = (0x41, 0x81)).state();
+        if profile.quirks().shift_uses_vy {
+            assert_eq!((state.v[1], state.v[0xF]), (0x02, 1), "{}", profile);
+        } else {
+            assert_eq!((state.v[1], state.v[0xF]), (0x82, 0), "{}", profile);
+        }
+    }
+}
+
+#[test]
+fn set_index() {
+    assert_eq!(run_modern(&[0xA123], |_| {}).i, 0x123);
+}
+
+#[test]
+fn jump_with_offset() {
+    for profile in PROFILES {
+        let state = run(profile, &[0xB300], |s| (s.v[0], s.v[3]) = (0x10, 0x20)).state();
+        let expected = if profile.quirks().jump_uses_vx { 0x320 } else { 0x310 };
+        assert_eq!(state.pc, expected, "{}", profile);
+    }
+}
+
+#[test]
+fn random_is_masked() {
+    assert_eq!(run_modern(&[0xC30F], |_| {}).v[3], 0x05);
+}
+
+#[test]
+fn draw_sets_pixels_and_collision() {
+    // 0xF0 is the top row of the 0 glyph
+    let emul = run(Profile::Modern, &[0xD125], |s| {
+        (s.v[1], s.v[2], s.i) = (10, 3, FONT_POS);
+    });
+    assert!(emul.pixel(10, 3) && emul.pixel(13, 3) && !emul.pixel(14, 3));
+    assert!(emul.pixel(10, 4) && !emul.pixel(11, 4));
+    assert_eq!(emul.state().v[0xF], 0);
+
+    let emul = run(Profile::Modern, &[0xD125, 0xD125], |s| {
+        (s.v[1], s.v[2], s.i) = (10, 3, FONT_POS);
+    });
+    assert!(!emul.pixel(10, 3));
+    assert_eq!(emul.state().v[0xF], 1);
+}
+
+#[test]
+fn draw_starting_position_wraps() {
+    let emul = run(Profile::Modern, &[0xD121], |s| {
+        (s.v[1], s.v[2], s.i) = (64 + 2, 32 + 1, FONT_POS);
+    });
+    assert!(emul.pixel(2, 1));
+}
+
+#[test]
+fn draw_at_edges_clips_or_wraps() {
+    for profile in PROFILES {
+        let emul = run(profile, &[0xD125], |s| {
+            (s.v[1], s.v[2], s.i) = (62, 30, FONT_POS);
+        });
+        assert!(emul.pixel(62, 30) && emul.pixel(63, 30), "{}", profile);
+        let wrapped = emul.pixel(0, 30) && emul.pixel(62, 0);
+        assert_eq!(wrapped, profile.quirks().wrap_sprites, "{}", profile);
+    }
+}
+
+#[test]
+fn skip_if_key() {
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &[0xE3, 0x9E]);
+    emul.hold_keys(1 << 7);
+    let mut state = emul.state();
+    state.v[3] = 7;
+    emul.set_state(state);
+    emul.step().unwrap();
+    assert_eq!(emul.state().pc, 0x204);
+
+    assert_eq!(run_modern(&[0xE39E], |s| s.v[3] = 7).pc, 0x202);
+}
+
+#[test]
+fn scripted_keys_are_read_once_per_frame() {
+    let script = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("opcodes.keys");
+    std::fs::write(&script, "4 5\n").unwrap();
+    let mut emul = emulator::builder()
+        .headless(true)
+        .instructions_per_frame(1)
+        .build();
+    // 200: SKP V5 / 202: JP 200 / 204: JP 204, so the SKP runs on even frames
+    emul.load(START, &[0xE5, 0x9E, 0x12, 0x00, 0x12, 0x04]);
+    emul.script_keys(script.to_str().unwrap()).unwrap();
+    let mut state = emul.state();
+    state.v[5] = 5;
+    emul.set_state(state);
+    emul.run_frames(4).unwrap();
+    assert_eq!(emul.state().pc, 0x200);
+    emul.run_frame().unwrap();
+    assert_eq!(emul.state().pc, 0x204);
+}
+
+#[test]
+fn skip_if_not_key() {
+    assert_eq!(run_modern(&[0xE3A1], |s| s.v[3] = 7).pc, 0x204);
+    // Only keys 0 to F exist
+    assert_eq!(run_modern(&[0xE3A1], |s| s.v[3] = 0x17).pc, 0x204);
+}
+
+#[test]
+fn audio_opcodes_only_on_xo_chip() {
+    for profile in PROFILES {
+        let xo_chip = profile == Profile::XoChip;
+        for inst in [0xF002u16, 0xF53A] {
+            let mut emul = machine(profile);
+            emul.load(START, &inst.to_be_bytes());
+            assert_eq!(emul.step().is_ok(), xo_chip, "{} {:04x}", profile, inst);
+        }
+        // F002 has no register, so FX02 with X set is unknown everywhere
+        let mut emul = machine(profile);
+        emul.load(START, &[0xF1, 0x02]);
+        assert!(
+            matches!(emul.step(), Err(Error::InvalidInstruction { inst: 0xF102, .. })),
+            "{}",
+            profile
+        );
+    }
+}
+
+#[test]
+fn invalid_instruction_is_an_error() {
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &[0xE3, 0x00]);
+    match emul.step() {
+        Err(Error::InvalidInstruction { address, inst }) => {
+            assert_eq!((address, inst), (0x200, 0xE300));
+        }
+        _ => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn timers() {
+    let state = run_modern(&[0xF307], |s| s.delay_timer = 42);
+    assert_eq!(state.v[3], 42);
+    let state = run_modern(&[0xF315], |s| s.v[3] = 42);
+    assert_eq!(state.delay_timer, 42);
+    let state = run_modern(&[0xF318], |s| s.v[3] = 42);
+    assert_eq!(state.sound_timer, 42);
+}
+
+#[test]
+fn timers_tick_once_per_frame() {
+    // A frame is 16 instructions
+    let code = [0x6000; 16];
+    let state = run_modern(&code, |s| (s.delay_timer, s.sound_timer) = (10, 1));
+    assert_eq!((state.delay_timer, state.sound_timer), (9, 0));
+}
+
+#[test]
+fn timers_count_down_to_zero() {
+    let mut emul = machine(Profile::Modern);
+    // LD V0, 30 / LD DT, V0 / LD ST, V0 / JP 206
+    emul.load(START, &[0x60, 0x1E, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06]);
+    emul.run_frames(10).unwrap();
+    let state = emul.state();
+    assert_eq!((state.delay_timer, state.sound_timer), (20, 20));
+    // Frames are counted from the start, and the timers stop at zero
+    emul.run_frames(100).unwrap();
+    let state = emul.state();
+    assert_eq!((state.delay_timer, state.sound_timer), (0, 0));
+}
+
+#[test]
+fn timers_run_while_waiting_for_a_key() {
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &[0xF3, 0x0A]);
+    let mut state = emul.state();
+    state.delay_timer = 5;
+    emul.set_state(state);
+    emul.run_frames(3).unwrap();
+    assert_eq!(emul.state().delay_timer, 2);
+    assert_eq!(emul.state().pc, 0x200);
+}
+
+#[test]
+fn instructions_per_frame() {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .instructions_per_frame(4)
+        .build();
+    emul.load(START, &[0x70, 0x01, 0x12, 0x00]);
+    assert!(emul.run_frame().unwrap());
+    assert_eq!((emul.cycles(), emul.frames()), (4, 1));
+    emul.step().unwrap();
+    assert!(emul.run_frame().unwrap());
+    assert_eq!((emul.cycles(), emul.frames()), (8, 2));
+    assert_eq!(emul.state().v[0], 4);
+}
+
+#[test]
+fn vip_timing() {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .timing(timing::Mode::Vip)
+        .build();
+    // 50 and 52 machine cycles, so the frame ends on the 52nd instruction
+    emul.load(START, &[0x70, 0x01, 0x12, 0x00]);
+    assert!(emul.run_frame().unwrap());
+    assert_eq!((emul.cycles(), emul.state().v[0]), (52, 26));
+
+    let aligned = timing::vip_cycles(0xD015, 8, false);
+    assert!(aligned < timing::vip_cycles(0xD015, 9, false));
+    assert!(aligned < timing::vip_cycles(0xD01F, 8, false));
+    assert!(timing::vip_cycles(0x3000, 0, false) < timing::vip_cycles(0x3000, 0, true));
+    assert!(timing::vip_cycles(0xF033, 0, false) < timing::vip_cycles(0xF033, 199, false));
+}
+
+// The ranges given for Builder::instructions_per_frame under VIP timing
+#[test]
+fn vip_instructions_per_frame() {
+    let loops: [(&[u8], std::ops::RangeInclusive<u64>); 3] = [
+        // ADD, JP
+        (&[0x70, 0x01, 0x12, 0x00], 45..=55),
+        // LD I, DRW 5 rows at a moving X, ADD, SKP, LD, SE, ADD registers, JP
+        (
+            &[
+                0xA0, 0x50, 0xD0, 0x15, 0x70, 0x01, 0xE5, 0x9E, 0x62, 0x00, 0x32, 0x00, 0x82,
+                0x14, 0x12, 0x00,
+            ],
+            25..=30,
+        ),
+        // LD I, then a sprite of 8 rows erased and drawn one pixel on
+        (
+            &[0xA0, 0x50, 0xD0, 0x18, 0x70, 0x01, 0xD0, 0x18, 0x12, 0x00],
+            8..=12,
+        ),
+    ];
+    for (code, range) in loops {
+        let mut emul = emulator::builder()
+            .headless(true)
+            .timing(timing::Mode::Vip)
+            .build();
+        emul.load(START, code);
+        emul.run_frames(60).unwrap();
+        let per_frame = emul.cycles() / emul.frames();
+        assert!(range.contains(&per_frame), "{:02x?}: {}", code, per_frame);
+    }
+}
+
+#[test]
+fn display_wait() {
+    for profile in PROFILES {
+        let mut emul = machine(profile);
+        emul.load(START, &[0xD0, 0x05, 0x12, 0x00]);
+        assert!(emul.run_frame().unwrap());
+        let expected = if profile.quirks().display_wait { 1 } else { 16 };
+        assert_eq!(emul.cycles(), expected, "{}", profile);
+
+        // Whatever the profile, the builder can turn it on or off
+        for (wait, expected) in [(true, 1), (false, 16)] {
+            let mut emul = emulator::builder()
+                .headless(true)
+                .quirks(profile)
+                .display_wait(wait)
+                .build();
+            emul.load(START, &[0xD0, 0x05, 0x12, 0x00]);
+            assert!(emul.run_frame().unwrap());
+            assert_eq!(emul.cycles(), expected, "{} {}", profile, wait);
+        }
+    }
+
+    let mut emul = emulator::builder()
+        .headless(true)
+        .quirks(Profile::Vip)
+        .timing(timing::Mode::Vip)
+        .build();
+    emul.load(START, &[0xD0, 0x05, 0x12, 0x00]);
+    emul.run_frame().unwrap();
+    assert_eq!(emul.cycles(), 1);
+    // The next frame has the draw's cost taken out already
+    emul.run_frame().unwrap();
+    assert!(emul.cycles() < 1 + 2 * timing::VIP_FRAME_BUDGET / 52);
+}
+
+#[test]
+fn display_wait_is_kept_in_movies() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("display-wait.movie");
+    let path = path.to_str().unwrap();
+    let code = [0xD0, 0x05, 0x12, 0x00];
+    let mut emul = emulator::builder()
+        .headless(true)
+        .display_wait(true)
+        .build();
+    emul.load(START, &code);
+    emul.record_movie(path).unwrap();
+
+    // Played back on a machine with the profile's setting, it still waits
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &code);
+    emul.play_movie(path).unwrap();
+    assert!(emul.run_frame().unwrap());
+    assert_eq!(emul.cycles(), 1);
+}
+
+#[test]
+fn finishing_ends_a_movie() {
+    // Driven frame by frame, as the TUI does, instead of through run
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("finish.movie");
+    let path = path.to_str().unwrap();
+    let code = [0x12, 0x00];
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &code);
+    emul.record_movie(path).unwrap();
+    emul.run_frames(5).unwrap();
+    emul.finish();
+    emul.finish();
+    let text = std::fs::read_to_string(path).unwrap();
+    assert_eq!(text.matches("end ").count(), 1);
+    assert!(text.ends_with("end 5\n"), "{}", text);
+
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &code);
+    emul.play_movie(path).unwrap();
+    emul.run_frames(100).unwrap();
+    assert_eq!(emul.frames(), 5);
+}
+
+#[test]
+fn add_to_index() {
+    for profile in PROFILES {
+        let state = run(profile, &[0xF31E], |s| (s.i, s.v[3], s.v[0xF]) = (0xFFE, 3, 7)).state();
+        let i = if profile.quirks().wide_addresses { 0x1001 } else { 0x001 };
+        assert_eq!(state.i, i, "{}", profile);
+        let vf = if profile.quirks().index_overflow_sets_vf { 1 } else { 7 };
+        assert_eq!(state.v[0xF], vf, "{}", profile);
+    }
+}
+
+#[test]
+fn wait_for_key() {
+    let state = run_modern(&[0xF30A], |_| {});
+    assert_eq!(state.pc, 0x200);
+
+    let mut emul = machine(Profile::Modern);
+    emul.load(START, &[0xF3, 0x0A]);
+    emul.hold_keys(1 << 0xB);
+    emul.step().unwrap();
+    assert_eq!(emul.state().pc, 0x202);
+    assert_eq!(emul.state().v[3], 0xB);
+}
+
+#[test]
+fn font_glyph_address() {
+    assert_eq!(run_modern(&[0xF329], |s| s.v[3] = 0).i, FONT_POS);
+    assert_eq!(run_modern(&[0xF329], |s| s.v[3] = 0xA).i, FONT_POS + 50);
+    let emul = run(Profile::Modern, &[0xF329], |s| s.v[3] = 0xA);
+    let glyph = &emul.memory()[FONT_POS as usize + 50..FONT_POS as usize + 55];
+    assert_eq!(glyph, &[0xF0, 0x90, 0xF0, 0x90, 0x90]);
+}
+
+#[test]
+fn big_font_glyph_address() {
+    let big = FONT_POS + 80;
+    assert_eq!(run_modern(&[0xF330], |s| s.v[3] = 0).i, big);
+    assert_eq!(run_modern(&[0xF330], |s| s.v[3] = 0x1A).i, big + 100);
+}
+
+#[test]
+fn binary_coded_decimal() {
+    let emul = run(Profile::Modern, &[0xF333], |s| (s.v[3], s.i) = (254, 0x300));
+    assert_eq!(&emul.memory()[0x300..0x303], &[2, 5, 4]);
+}
+
+#[test]
+fn store_and_load_registers() {
+    for profile in PROFILES {
+        let emul = run(profile, &[0xF255], |s| {
+            s.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+            s.i = 0x300;
+        });
+        assert_eq!(&emul.memory()[0x300..0x304], &[1, 2, 3, 0], "{}", profile);
+        let i = if profile.quirks().load_store_increments_i { 0x303 } else { 0x300 };
+        assert_eq!(emul.state().i, i, "{}", profile);
+
+        let mut emul = machine(profile);
+        emul.load(0x300, &[9, 8, 7, 6]);
+        emul.load(START, &[0xF2, 0x65]);
+        let mut state = emul.state();
+        state.i = 0x300;
+        emul.set_state(state);
+        emul.step().unwrap();
+        assert_eq!(&emul.state().v[..4], &[9, 8, 7, 0], "{}", profile);
+        assert_eq!(emul.state().i, i, "{}", profile);
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    // Overwrites the 6005 at 0x206 with 6107 before reaching it
+    let code: [u16; 4] = [0x6061, 0x6107, 0xF155, 0x6005];
+    let mut emul = emulator::builder().headless(true).build();
+    let bytes: Vec<u8> = code.iter().flat_map(|inst| inst.to_be_bytes()).collect();
+    emul.load(START, &bytes);
+    let mut state = emul.state();
+    state.i = 0x206;
+    emul.set_state(state);
+    for _ in 0..4 {
+        emul.step().unwrap();
+    }
+    assert_eq!(emul.state().v[1], 0x07);
+    assert_eq!(emul.state().v[0], 0x61);
+}
@@ -0,0 +1,82 @@
+// CXNN generators: the same seed gives the same bytes, and a saved state carries on where it left off
+use rusty::emulator::rng::{self, Mode, Rng};
+use rusty::emulator::{self, Emulator};
+
+const MODES: [&str; 3] = ["xorshift", "vip", "fixed:01,80,ff"];
+
+fn bytes(rng: &mut Rng, count: usize) -> Vec<u8> {
+    return (0..count).map(|_| rng.next_byte()).collect();
+}
+
+// A loop of CXNN into V0 to VF: RND VX, FF for each, then JP back
+fn machine(mode: Mode, seed: u64) -> Emulator {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .rng(mode)
+        .seed(seed)
+        .build();
+    let mut code: Vec<u8> = (0..16).flat_map(|x| [0xC0 | x, 0xFF]).collect();
+    code.extend([0x12, 0x00]);
+    emul.load(0x200, &code);
+    return emul;
+}
+
+#[test]
+fn deterministic() {
+    for text in MODES {
+        let mode: Mode = text.parse().unwrap();
+        let first = bytes(&mut rng::create(mode.clone(), 7), 64);
+        assert_eq!(
+            first,
+            bytes(&mut rng::create(mode.clone(), 7), 64),
+            "{}",
+            text
+        );
+
+        let mut a = machine(mode.clone(), 7);
+        let mut b = machine(mode.clone(), 7);
+        a.run_frames(20).unwrap();
+        b.run_frames(20).unwrap();
+        assert_eq!(a.state(), b.state(), "{}", text);
+    }
+    // Only the fixed bytes ignore the seed
+    let xorshift = bytes(&mut rng::create(Mode::Xorshift, 1), 16);
+    assert_ne!(xorshift, bytes(&mut rng::create(Mode::Xorshift, 2), 16));
+    let vip = bytes(&mut rng::create(Mode::Vip, 1), 16);
+    assert_ne!(vip, bytes(&mut rng::create(Mode::Vip, 2), 16));
+    let fixed = bytes(&mut rng::create(Mode::Fixed(vec![1, 0x80, 0xFF]), 1), 6);
+    assert_eq!(fixed, [1, 0x80, 0xFF, 1, 0x80, 0xFF]);
+}
+
+#[test]
+fn text_round_trip() {
+    for text in MODES {
+        let mut rng = rng::create(text.parse().unwrap(), 42);
+        bytes(&mut rng, 5);
+        let mut copy: Rng = rng.to_string().parse().unwrap();
+        assert_eq!(copy, rng, "{}", text);
+        assert_eq!(bytes(&mut copy, 32), bytes(&mut rng, 32), "{}", text);
+    }
+    assert!("xorshift:zz".parse::<Rng>().is_err());
+    assert!("fixed:0".parse::<Rng>().is_err());
+}
+
+#[test]
+fn save_state_round_trip() {
+    for text in MODES {
+        let mode: Mode = text.parse().unwrap();
+        let mut emul = machine(mode.clone(), 3);
+        emul.run_frames(5).unwrap();
+        let saved = emul.state();
+        emul.run_frames(10).unwrap();
+        let expected = emul.state();
+
+        // Restoring into a machine seeded differently still replays the same bytes
+        let mut other = machine(mode, 99);
+        other.run_frames(5).unwrap();
+        other.set_state(saved.clone());
+        assert_eq!(other.state(), saved, "{}", text);
+        other.run_frames(10).unwrap();
+        assert_eq!(other.state(), expected, "{}", text);
+    }
+}
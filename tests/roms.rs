@@ -0,0 +1,69 @@
+// Finding the ROMs for the launcher: grouped by directory, with their notes and profile
+use rusty::emulator::quirks::Profile;
+use rusty::roms;
+use std::path::{Path, PathBuf};
+
+fn root() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("roms");
+    let _ = std::fs::remove_dir_all(&dir);
+    return dir;
+}
+
+fn write(root: &Path, name: &str, bytes: &[u8]) {
+    let path = root.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, bytes).unwrap();
+}
+
+#[test]
+fn scan() {
+    let root = root();
+    let plain = [0x12, 0x00];
+    write(&root, "games/Tetris.ch8", &plain);
+    write(&root, "games/Tetris.txt", b"Rotate with 4\n");
+    write(&root, "games/Brix.ch8", &plain);
+    write(
+        &root,
+        "demos/Hires.sc8",
+        &[0x30, 0x01, 0x00, 0xFF, 0x00, 0xFD],
+    );
+    write(&root, "demos/Beep.XO8", &[0xF0, 0x02, 0x12, 0x00]);
+    write(&root, "Maze.ch8", &plain);
+    write(&root, "README.txt", b"Not a ROM\n");
+    write(&root, "games/Brix.bak", &plain);
+    std::fs::create_dir_all(root.join("empty")).unwrap();
+
+    let found: Vec<(String, String)> = roms::scan(&root)
+        .iter()
+        .map(|entry| (entry.category.clone(), entry.name.clone()))
+        .collect();
+    let expected = [
+        (".", "Maze"),
+        ("demos", "Beep"),
+        ("demos", "Hires"),
+        ("games", "Brix"),
+        ("games", "Tetris"),
+    ];
+    assert_eq!(
+        found,
+        expected.map(|(category, name)| (category.to_string(), name.to_string()))
+    );
+
+    let entries = roms::scan(&root);
+    let profiles: Vec<Profile> = entries.iter().map(|entry| entry.profile).collect();
+    assert_eq!(
+        profiles,
+        [
+            Profile::Vip,
+            Profile::XoChip,
+            Profile::Schip,
+            Profile::Vip,
+            Profile::Vip
+        ]
+    );
+    let notes: Vec<Option<&str>> = entries.iter().map(|entry| entry.notes.as_deref()).collect();
+    assert_eq!(notes, [None, None, None, None, Some("Rotate with 4\n")]);
+    assert_eq!(entries[4].path, root.join("games/Tetris.ch8"));
+
+    assert!(roms::scan(&root.join("missing")).is_empty());
+}
@@ -0,0 +1,136 @@
+// Screenshots in each format, with scale and palette
+use rusty::emulator::export::Format;
+use rusty::emulator::orientation::Orientation;
+use rusty::emulator::{self, Emulator};
+
+fn screen(scale: usize) -> Emulator {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .scale(scale)
+        .palette("102030,f0e0d0".parse().unwrap())
+        .build();
+    // Draw the 0 glyph at 0, 0
+    emul.load(0x200, &[0xA0, 0x50, 0xD0, 0x05]);
+    emul.step().unwrap();
+    emul.step().unwrap();
+    return emul;
+}
+
+fn path(name: &str) -> String {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    return path.to_str().unwrap().to_string();
+}
+
+#[test]
+fn png_is_scaled_with_the_palette() {
+    let file = path("screen.png");
+    screen(3).save_screen(&file).unwrap();
+    let decoder = png::Decoder::new(std::fs::File::open(&file).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.width, info.height), (192, 96));
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+    // The glyph's top row is 4 pixels wide, so 12 once scaled
+    let row = &pixels[..info.line_size];
+    assert_eq!(row[..3], [0xF0, 0xE0, 0xD0]);
+    assert_eq!(row[11 * 3..12 * 3], [0xF0, 0xE0, 0xD0]);
+    assert_eq!(row[12 * 3..13 * 3], [0x10, 0x20, 0x30]);
+}
+
+#[test]
+fn pbm_and_text_grid() {
+    let file = path("screen.pbm");
+    screen(2).save_screen(&file).unwrap();
+    let text = std::fs::read_to_string(&file).unwrap();
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("P1"));
+    assert_eq!(lines.next(), Some("128 64"));
+    let row = lines.next().unwrap();
+    assert!(row.starts_with("1 1 1 1 1 1 1 1 0"));
+    assert_eq!(lines.count(), 63);
+
+    // One character per pixel whatever the scale
+    let file = path("screen.txt");
+    screen(2).save_screen(&file).unwrap();
+    let text = std::fs::read_to_string(&file).unwrap();
+    assert_eq!(text.lines().count(), 32);
+    assert!(text.starts_with("####...."));
+    assert_eq!(text.lines().nth(1).unwrap()[..5], *"#..#.");
+}
+
+#[test]
+fn hotkey_formats() {
+    for name in ["png", "pbm", "txt"] {
+        assert_eq!(name.parse::<Format>().unwrap().extension(), name);
+    }
+    assert!("jpg".parse::<Format>().is_err());
+
+    // Saved in the working directory, like the hotkey's
+    let mut emul = emulator::builder()
+        .headless(true)
+        .screenshot_format(Format::Text)
+        .build();
+    emul.load(0x200, &[0xA0, 0x50, 0xD0, 0x05]);
+    emul.step().unwrap();
+    emul.step().unwrap();
+    let file = emul.screenshot().unwrap();
+    let text = std::fs::read_to_string(&file).unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert!(file.starts_with("rusty-") && file.ends_with(".txt"), "{}", file);
+    assert!(text.starts_with("####...."));
+}
+
+#[test]
+fn rotation_and_flips() {
+    let grid = |rotation: u16, flip_horizontal: bool, flip_vertical: bool| {
+        let file = path("turned.txt");
+        let mut emul = emulator::builder()
+            .headless(true)
+            .orientation(Orientation {
+                rotation,
+                flip_horizontal,
+                flip_vertical,
+            })
+            .build();
+        // A single pixel at column 1, row 0
+        emul.load(
+            0x200,
+            &[0x60, 0x01, 0xA2, 0x08, 0xD0, 0x21, 0x00, 0x00, 0x80],
+        );
+        emul.step().unwrap();
+        emul.step().unwrap();
+        emul.step().unwrap();
+        emul.save_screen(&file).unwrap();
+        let text = std::fs::read_to_string(&file).unwrap();
+        let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+        let row = lines.iter().position(|line| line.contains('#')).unwrap();
+        let column = lines[row].find('#').unwrap();
+        return (lines.len(), lines[0].len(), row, column);
+    };
+    assert_eq!(grid(0, false, false), (32, 64, 0, 1));
+    assert_eq!(grid(90, false, false), (64, 32, 1, 31));
+    assert_eq!(grid(180, false, false), (32, 64, 31, 62));
+    assert_eq!(grid(270, false, false), (64, 32, 62, 0));
+    assert_eq!(grid(0, true, false), (32, 64, 0, 62));
+    assert_eq!(grid(0, false, true), (32, 64, 31, 1));
+    assert_eq!(grid(90, true, true), (64, 32, 62, 0));
+}
+
+#[test]
+fn rotation_leaves_collisions_alone() {
+    let mut emul = emulator::builder()
+        .headless(true)
+        .orientation(Orientation {
+            rotation: 90,
+            ..Default::default()
+        })
+        .build();
+    // Draw the same sprite twice: the second erases it and sets VF
+    emul.load(0x200, &[0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05]);
+    for _ in 0..3 {
+        emul.step().unwrap();
+    }
+    assert_eq!(emul.state().v[0xF], 1);
+    assert!(!emul.pixel(0, 0));
+}
@@ -0,0 +1,113 @@
+// Per-ROM settings from the command line, sidecar files and roms.toml
+use rusty::emulator::keymap;
+use rusty::emulator::quirks::Profile;
+use rusty::emulator::settings::{self, Settings};
+use std::path::PathBuf;
+
+fn path(name: &str) -> PathBuf {
+    return std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+}
+
+// A ROM with its own directory, so that its sidecar file starts out missing
+fn write_rom(name: &str, bytes: &[u8]) -> String {
+    let dir = path(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let rom = dir.join("game.ch8");
+    std::fs::write(&rom, bytes).unwrap();
+    return rom.to_str().unwrap().to_string();
+}
+
+#[test]
+fn keymaps() {
+    let default = keymap::default();
+    assert_eq!(default.key('x'), Some(0));
+    assert_eq!(default.key('4'), Some(0xC));
+    assert_eq!(default.key('V'), Some(0xF));
+    assert_eq!(default.key('p'), None);
+    assert_eq!(default.to_string(), "x123qweasdzc4rfv");
+    assert!("x123qweasdzc4rf".parse::<keymap::Keymap>().is_err());
+    assert!("x123qweasdzc4rfx".parse::<keymap::Keymap>().is_err());
+    assert!("x123qweasdzc4rf!".parse::<keymap::Keymap>().is_err());
+}
+
+#[test]
+fn resolution_order() {
+    let bytes = [0x00, 0xE0, 0x12, 0x00];
+    let rom = write_rom("resolution", &bytes);
+    let config = path("resolution/roms.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[defaults]\nipf = 30\nkeymap = \"0123456789abcdef\"\n\n\
+             [roms.{}]\nipf = 20\nquirks = \"vip\"\npalette = \"000000,00ff00\"\n\n\
+             [roms.0000000000000000]\nquirks = \"xochip\"\n",
+            settings::rom_hash(&bytes)
+        ),
+    )
+    .unwrap();
+    let config = settings::load_config(&config).unwrap();
+
+    // Without a sidecar the ROM's entry wins over the defaults
+    let found = settings::resolve(&Settings::default(), &rom, &config).unwrap();
+    assert_eq!(found.instructions_per_frame, Some(20));
+    assert_eq!(found.quirks, Some(Profile::Vip));
+    assert_eq!(found.keymap, Some("0123456789abcdef".parse().unwrap()));
+    assert_eq!(found.palette, Some("000000,00ff00".parse().unwrap()));
+
+    // The sidecar wins over the ROM's entry
+    std::fs::write(settings::sidecar(&rom), "quirks = \"schip\"\n").unwrap();
+    let found = settings::resolve(&Settings::default(), &rom, &config).unwrap();
+    assert_eq!(found.quirks, Some(Profile::Schip));
+    assert_eq!(found.instructions_per_frame, Some(20));
+
+    // And the command line wins over everything
+    let given = Settings {
+        instructions_per_frame: Some(8),
+        quirks: Some(Profile::Modern),
+        ..Default::default()
+    };
+    let found = settings::resolve(&given, &rom, &config).unwrap();
+    assert_eq!(found.instructions_per_frame, Some(8));
+    assert_eq!(found.quirks, Some(Profile::Modern));
+    assert_eq!(found.palette, Some("000000,00ff00".parse().unwrap()));
+
+    // Nothing at all leaves everything to the caller's defaults
+    let other = write_rom("resolution-none", &[0x12, 0x00]);
+    let missing = settings::load_config(&path("resolution-none/roms.toml")).unwrap();
+    let found = settings::resolve(&Settings::default(), &other, &missing).unwrap();
+    assert_eq!(found, Settings::default());
+}
+
+#[test]
+fn saving_and_bad_files() {
+    let rom = write_rom("saving", &[0x12, 0x00]);
+    let sidecar = settings::sidecar(&rom);
+    assert!(sidecar.ends_with("game.ch8.toml"));
+    let saved = Settings {
+        instructions_per_frame: Some(12),
+        quirks: Some(Profile::XoChip),
+        keymap: Some(keymap::default()),
+        palette: Some("102030,f0e0d0".parse().unwrap()),
+    };
+    settings::save(&sidecar, &saved).unwrap();
+    assert_eq!(settings::load(&sidecar).unwrap(), saved);
+
+    // Settings left out aren't written
+    let partial = Settings {
+        quirks: Some(Profile::Vip),
+        ..Default::default()
+    };
+    settings::save(&sidecar, &partial).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&sidecar).unwrap(),
+        "quirks = \"vip\"\n"
+    );
+    assert_eq!(settings::load(&sidecar).unwrap(), partial);
+
+    for bad in ["quirks = \"cosmac\"\n", "speed = 3\n", "ipf = \"fast\"\n"] {
+        std::fs::write(&sidecar, bad).unwrap();
+        let error = settings::load(&sidecar).unwrap_err();
+        assert!(error.starts_with(sidecar.to_str().unwrap()), "{}", error);
+    }
+}
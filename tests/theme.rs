@@ -0,0 +1,82 @@
+// Terminal themes: colours, presets and theme files
+use rusty::emulator::theme::{self, Color};
+
+#[test]
+fn colors() {
+    let parse = |text: &str| text.parse::<Color>();
+    assert_eq!(parse("default"), Ok(Color::Default));
+    assert_eq!(parse("red"), Ok(Color::Ansi(1)));
+    assert_eq!(parse("bright-white"), Ok(Color::Ansi(15)));
+    assert_eq!(parse("208"), Ok(Color::Indexed(208)));
+    assert_eq!(parse("#FFB000"), Ok(Color::Rgb([0xFF, 0xB0, 0x00])));
+    assert!(parse("256").is_err());
+    assert!(parse("#FFB0").is_err());
+    assert!(parse("mauve").is_err());
+}
+
+#[test]
+fn presets_and_escapes() {
+    // The default draws emoji with no escapes at all
+    let emoji = theme::default();
+    assert_eq!(emoji.pixel(0), "⬛");
+    assert_eq!(emoji.pixel(1), "⬜");
+    assert_eq!(emoji.reset(), "");
+
+    let contrast = theme::named("high-contrast").unwrap();
+    assert_eq!(contrast.pixel(0), "\x1B[30;40m██");
+    assert_eq!(contrast.pixel(1), "\x1B[97;40m██");
+    assert_eq!(contrast.reset(), "\x1B[0m");
+
+    let amber = theme::named("amber").unwrap();
+    assert_eq!(amber.pixel(1), "\x1B[38;2;255;176;0;48;2;26;15;0m██");
+
+    for name in ["octo", "lcd", "amber", "high-contrast", "emoji"] {
+        assert!(theme::named(name).is_some(), "{}", name);
+    }
+
+    let mut mixed = theme::default();
+    mixed
+        .set_colors(&["default".to_string(), "208".to_string()])
+        .unwrap();
+    assert_eq!(mixed.pixel(0), "\x1B[39;49m⬛");
+    assert_eq!(mixed.pixel(1), "\x1B[38;5;208;49m⬜");
+    assert_eq!(mixed.colors[3], Color::Indexed(208));
+}
+
+#[test]
+fn theme_files() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("theme.toml");
+    let path = path.to_str().unwrap();
+    std::fs::write(
+        path,
+        "base = \"lcd\"\nglyphs = [\"  \", \"##\", \"%%\", \"@@\"]\n",
+    )
+    .unwrap();
+    let loaded = theme::find(path).unwrap();
+    assert_eq!(loaded.colors, theme::named("lcd").unwrap().colors);
+    assert_eq!(loaded.glyphs[2], "%%");
+
+    std::fs::write(path, "colors = [\"red\", \"blue\", \"green\"]\n").unwrap();
+    assert!(theme::load(path).is_err());
+    std::fs::write(path, "colours = [\"red\", \"blue\"]\n").unwrap();
+    assert!(theme::load(path).is_err());
+}
+
+#[test]
+fn fading() {
+    let amber = theme::named("amber").unwrap();
+    assert_eq!(amber.faded(1.0), amber.pixel(1));
+    assert_eq!(amber.faded(0.0), amber.pixel(0));
+    // Halfway from 1A0F00 to FFB000
+    assert_eq!(amber.faded(0.5), "\x1B[38;2;141;96;0;48;2;26;15;0m██");
+
+    // 256 colours fade through the nearest entries, here the grey ramp
+    let mut grey = theme::default();
+    grey.set_colors(&["16".to_string(), "231".to_string()])
+        .unwrap();
+    assert_eq!(grey.faded(0.5), "\x1B[38;5;244;48;5;16m⬜");
+
+    // The 16 ANSI colours can't fade, so the pixel stays lit until it goes out
+    let contrast = theme::named("high-contrast").unwrap();
+    assert_eq!(contrast.faded(0.25), contrast.pixel(1));
+}
@@ -0,0 +1,82 @@
+// Recorded video, as gif and as raw rgb24
+use rusty::emulator;
+
+const START: usize = 0x200;
+
+// Draws the 0 glyph in the top left corner every 30 frames, so that it blinks
+const BLINK: [u8; 16] = [
+    0xA0, 0x50, // I = font
+    0xD0, 0x05, // draw at 0, 0
+    0x61, 0x1E, // V1 = 30
+    0xF1, 0x15, // DT = V1
+    0xF1, 0x07, // V1 = DT
+    0x31, 0x00, // skip if V1 == 0
+    0x12, 0x08, // wait
+    0x12, 0x02, // draw again
+];
+
+fn record(name: &str, frames: u64, scale: usize) -> String {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let path = path.to_str().unwrap().to_string();
+    let mut emul = emulator::builder()
+        .headless(true)
+        .scale(scale)
+        .palette("102030,f0e0d0".parse().unwrap())
+        .build();
+    emul.load(START, &BLINK);
+    emul.record_video(&path).unwrap();
+    emul.run_frames(frames).unwrap();
+    emul.stop_video();
+    return path;
+}
+
+#[test]
+fn gif_frames_last_until_the_screen_changes() {
+    let path = record("blink.gif", 120, 2);
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options
+        .read_info(std::fs::File::open(&path).unwrap())
+        .unwrap();
+    assert_eq!((decoder.width(), decoder.height()), (128, 64));
+    assert_eq!(
+        decoder.global_palette().unwrap(),
+        [0x10, 0x20, 0x30, 0xF0, 0xE0, 0xD0]
+    );
+    let mut delays = Vec::new();
+    let mut lit = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().unwrap() {
+        delays.push(frame.delay);
+        lit.push(frame.buffer[0] == 1);
+    }
+    assert_eq!(lit, [true, false, true, false]);
+    // Two seconds in all, with every blink half a second long
+    assert_eq!(delays.iter().sum::<u16>(), 200);
+    assert!(delays[1..3]
+        .iter()
+        .all(|delay| *delay == 50 || *delay == 51));
+}
+
+#[test]
+fn raw_video_has_every_frame() {
+    let path = record("blink.rgb", 10, 3);
+    let bytes = std::fs::read(&path).unwrap();
+    let frame = 64 * 3 * 32 * 3 * 3;
+    assert_eq!(bytes.len(), 10 * frame);
+    assert_eq!(bytes[..3], [0xF0, 0xE0, 0xD0]);
+    assert_eq!(bytes[frame - 3..frame], [0x10, 0x20, 0x30]);
+}
+
+#[test]
+fn gif_too_large_is_an_error() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("huge.gif");
+    let path = path.to_str().unwrap();
+    // 64 pixels at scale 1024 is wider than a gif can be
+    let mut emul = emulator::builder().headless(true).scale(1024).build();
+    let error = emul.record_video(path).unwrap_err();
+    assert!(error.contains("too large"), "{}", error);
+    // Just below the limit is fine
+    let mut emul = emulator::builder().headless(true).scale(1023).build();
+    emul.record_video(path).unwrap();
+    emul.stop_video();
+}